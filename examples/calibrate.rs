@@ -0,0 +1,36 @@
+extern crate qwiic_adc_rs;
+
+use std::env;
+use std::io;
+
+use qwiic_adc_rs::*;
+
+// Interactive offset and gain calibration of A0 at the ±2.048 V range
+//
+// Usage: cargo run --example calibrate [reference volts] [output file]
+fn main() {
+    let mut args = env::args().skip(1);
+    let reference: f32 = args.next()
+        .map(|arg| arg.parse().expect("Reference must be a voltage, e.g. 1.0"))
+        .unwrap_or(1.0);
+    let path = args.next().unwrap_or_else(|| "calibration.txt".to_string());
+
+    let mut adc = QwiicADC::new(QwiicADCConfig::default(), "/dev/i2c-1", 0x48)
+        .expect("Could not init ADC device");
+    adc.init().expect("Failed to initialize ADC");
+
+    let correction = adc.calibrate(Mux::Single0, PGA::Two, reference, 32, |step| {
+        match step {
+            CalibrationStep::Short => println!("Short A0 to GND and press enter"),
+            CalibrationStep::Reference(volts) => println!("Connect A0 to the {volts} V reference and press enter"),
+        }
+        io::stdin().read_line(&mut String::new()).expect("Failed to read stdin");
+    }).expect("Failed to calibrate");
+    println!("Offset {:.5} V, gain {:.5}", correction.offset, correction.gain);
+
+    let volts = adc.read_volts(Mux::Single0, PGA::Two, SampleRates::S1600Hz).expect("Failed to read");
+    println!("Calibrated reading: {volts:.4} V");
+
+    adc.calibration().save(&path).expect("Failed to save calibration");
+    println!("Saved to {path}");
+}
@@ -0,0 +1,434 @@
+//! Background acquisition
+//!
+//! An [`Acquisition`] moves the ADC onto its own thread, which keeps it in
+//! continuous mode and pushes timestamped samples into a bounded lock-free
+//! ring buffer. The caller collects samples with `recv` or `drain` at its own
+//! pace; samples that do not fit in the buffer are dropped and counted.
+
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{AdcError, Mux, QwiicADC, Sample, SampleRates, PGA};
+
+/// Default number of samples held by the ring buffer
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// Configuration for a background acquisition
+#[derive(Debug, Clone)]
+pub struct AcquisitionConfig {
+    /// Inputs sampled in order; a single input streams without switching
+    pub inputs: Vec<Mux>,
+    /// PGA gain setting used for every input (default: `PGA::Two`)
+    pub gain: PGA,
+    /// Data rate used for every input (default: 1600 SPS)
+    pub rate: SampleRates,
+    /// Number of samples the ring buffer can hold (default: 1024)
+    pub capacity: usize,
+}
+
+impl AcquisitionConfig {
+    /// Create a configuration sampling a single-ended channel
+    ///
+    /// # Arguments
+    /// * `channel` - Channel number (must be 0-3)
+    pub fn channel(channel: u8) -> Result<AcquisitionConfig, AdcError> {
+        Ok(AcquisitionConfig::scan(&[Mux::single(channel)?]))
+    }
+
+    /// Create a configuration cycling through a list of inputs
+    ///
+    /// # Arguments
+    /// * `inputs` - Single-ended or differential inputs, sampled in order
+    pub fn scan(inputs: &[Mux]) -> AcquisitionConfig {
+        AcquisitionConfig {
+            inputs: inputs.to_vec(),
+            gain: PGA::Two,
+            rate: SampleRates::S1600Hz,
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+
+    /// Set the PGA gain
+    pub fn with_gain(mut self, gain: PGA) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Set the data rate
+    pub fn with_sample_rate(mut self, rate: SampleRates) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Set the ring buffer capacity in samples
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+}
+
+/// State shared between the acquisition thread and its owner
+struct Shared {
+    buffer: RingBuffer<Sample>,
+    running: AtomicBool,
+    finished: AtomicBool,
+    overflows: AtomicU64,
+    missed: AtomicU64,
+    error: Mutex<Option<AdcError>>,
+}
+
+/// Continuous-mode sampling on a background thread
+///
+/// Dropping the acquisition stops the thread and releases the device; use
+/// `stop` to get the `QwiicADC` back instead.
+pub struct Acquisition {
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<QwiicADC>>,
+    poll_interval: Duration,
+}
+
+impl Acquisition {
+    /// Start sampling on a background thread
+    ///
+    /// # Arguments
+    /// * `adc` - Device to sample, returned by `stop`
+    /// * `config` - Inputs, gain, rate and buffer size
+    pub fn start(adc: QwiicADC, config: AcquisitionConfig) -> Acquisition {
+        let poll_interval = Duration::from_secs_f32(1.0 / adc.data_rate_hz(config.rate));
+        let shared = Arc::new(Shared {
+            buffer: RingBuffer::new(config.capacity.max(1)),
+            running: AtomicBool::new(true),
+            finished: AtomicBool::new(false),
+            overflows: AtomicU64::new(0),
+            missed: AtomicU64::new(0),
+            error: Mutex::new(None),
+        });
+
+        let thread_shared = Arc::clone(&shared);
+        let handle = thread::spawn(move || {
+            let mut adc = adc;
+            if let Err(e) = run(&mut adc, &config, &thread_shared) {
+                *thread_shared.error.lock().unwrap() = Some(e);
+            }
+            let _ = adc.stop_continuous();
+            thread_shared.finished.store(true, Ordering::Release);
+            adc
+        });
+
+        Acquisition {
+            shared,
+            handle: Some(handle),
+            poll_interval,
+        }
+    }
+
+    /// Take the next sample, blocking until one is available
+    ///
+    /// # Returns
+    /// * `Some(sample)` - Oldest buffered sample
+    /// * `None` once the acquisition thread has stopped and the buffer is empty
+    pub fn recv(&mut self) -> Option<Sample> {
+        loop {
+            if let Some(sample) = self.try_recv() {
+                return Some(sample);
+            }
+            if self.shared.finished.load(Ordering::Acquire) {
+                return self.try_recv();
+            }
+            thread::sleep(self.poll_interval);
+        }
+    }
+
+    /// Take the next sample, waiting at most `timeout` for one to arrive
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Option<Sample> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(sample) = self.try_recv() {
+                return Some(sample);
+            }
+            let now = Instant::now();
+            if now >= deadline || self.shared.finished.load(Ordering::Acquire) {
+                return self.try_recv();
+            }
+            thread::sleep(self.poll_interval.min(deadline - now));
+        }
+    }
+
+    /// Take the next sample if one is buffered
+    pub fn try_recv(&mut self) -> Option<Sample> {
+        // `&mut self` guarantees there is a single consumer
+        self.shared.buffer.pop()
+    }
+
+    /// Take every buffered sample without blocking
+    pub fn drain(&mut self) -> Vec<Sample> {
+        let mut samples = Vec::with_capacity(self.shared.buffer.len());
+        while let Some(sample) = self.try_recv() {
+            samples.push(sample);
+        }
+        samples
+    }
+
+    /// Number of samples currently buffered
+    pub fn len(&self) -> usize {
+        self.shared.buffer.len()
+    }
+
+    /// Check if no samples are buffered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of samples dropped because the ring buffer was full
+    pub fn overflows(&self) -> u64 {
+        self.shared.overflows.load(Ordering::Relaxed)
+    }
+
+    /// Number of conversions the acquisition thread failed to read in time
+    ///
+    /// Only counted when sampling a single input. When scanning several inputs
+    /// each sample waits out the conversions after its channel switch, so the
+    /// conversions in between are discarded on purpose and not counted.
+    pub fn missed(&self) -> u64 {
+        self.shared.missed.load(Ordering::Relaxed)
+    }
+
+    /// Check if the acquisition thread is still sampling
+    pub fn is_running(&self) -> bool {
+        !self.shared.finished.load(Ordering::Acquire)
+    }
+
+    /// Take the error that stopped the acquisition thread, if any
+    pub fn take_error(&self) -> Option<AdcError> {
+        self.shared.error.lock().unwrap().take()
+    }
+
+    /// Stop sampling and return the device
+    ///
+    /// Samples still in the buffer are discarded; call `drain` first to keep them.
+    ///
+    /// # Returns
+    /// * `Ok(adc)` - The device
+    /// * `Err(AdcError::AcquisitionPanicked)` if the acquisition thread panicked
+    pub fn stop(mut self) -> Result<QwiicADC, AdcError> {
+        self.shared.running.store(false, Ordering::Release);
+        self.handle.take()
+            .ok_or(AdcError::AcquisitionPanicked)?
+            .join()
+            .map_err(|_| AdcError::AcquisitionPanicked)
+    }
+}
+
+impl Drop for Acquisition {
+    fn drop(&mut self) {
+        self.shared.running.store(false, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Acquisition thread body
+fn run(adc: &mut QwiicADC, config: &AcquisitionConfig, shared: &Shared) -> Result<(), AdcError> {
+    let keep_running = || shared.running.load(Ordering::Acquire);
+    let publish = |sample: Sample| {
+        if !shared.buffer.push(sample) {
+            shared.overflows.fetch_add(1, Ordering::Relaxed);
+        }
+    };
+
+    match config.inputs.as_slice() {
+        [] => Ok(()),
+        [input] => {
+            let mut stream = adc.stream_input(*input, config.gain, config.rate)?;
+            let mut missed = 0;
+            while keep_running() {
+                let sample = stream.next().expect("continuous-mode stream never ends")?;
+                publish(sample);
+                shared.missed.fetch_add(stream.missed() - missed, Ordering::Relaxed);
+                missed = stream.missed();
+            }
+            Ok(())
+        },
+        inputs => {
+            adc.start_continuous_input(inputs[0], config.gain, config.rate)?;
+            thread::sleep(adc.settling_time(config.rate as u16));
+
+            let mut seq = 0;
+            while keep_running() {
+                for &input in inputs {
+                    if seq > 0 {
+                        adc.switch_channel_continuous(input)?;
+                    }
+                    let timestamp = Instant::now();
+                    let value = adc.read_last_conversion()?;
+                    adc.count_clipping(input, value);
+                    let volts = adc.counts_to_volts(adc.raw_to_signed(value) as f32, config.gain);
+                    publish(Sample { input, value, volts, timestamp, seq });
+                    seq += 1;
+                }
+            }
+            Ok(())
+        },
+    }
+}
+
+/// Bounded single-producer single-consumer queue
+///
+/// `head` and `tail` count every pop and push since creation; their difference
+/// is the number of buffered items. Only the producer advances `tail` and only
+/// the consumer advances `head`.
+struct RingBuffer<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// SAFETY: Sharing the buffer only hands out `T` by value, so `T: Send` is
+// enough. There is exactly one producer (the acquisition thread calling `push`)
+// and one consumer (the owning `Acquisition` calling `pop`/`drain`). A slot in
+// `tail..head + capacity` is touched only by the producer, and a slot in
+// `head..tail` only by the consumer; an index moves from one side to the other
+// only through a Release store of `tail` or `head` that the other side reads
+// with Acquire, so no slot is ever accessed from both threads at once.
+unsafe impl<T: Send> Sync for RingBuffer<T> {}
+
+impl<T: Copy> RingBuffer<T> {
+    fn new(capacity: usize) -> RingBuffer<T> {
+        RingBuffer {
+            slots: (0..capacity).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+
+    /// Append an item, returning `false` if the buffer is full (producer only)
+    fn push(&self, item: T) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == self.slots.len() {
+            return false;
+        }
+
+        // SAFETY: `tail - head < capacity`, so this slot is outside `head..tail`
+        // and the consumer will not read it until the Release store of `tail`
+        // below. The Acquire load of `head` ensures the consumer has finished
+        // reading whatever item the slot held before.
+        unsafe { (*self.slots[tail % self.slots.len()].get()).write(item) };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Remove the oldest item (consumer only)
+    fn pop(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+
+        // SAFETY: `head < tail`, so the producer initialized this slot before
+        // its Release store of `tail`, which the Acquire load above observed;
+        // the write is therefore visible and `assume_init` reads a valid `T`.
+        // The producer will not reuse the slot until `head` is advanced below,
+        // and `T: Copy` means reading it out leaves nothing to drop.
+        let item = unsafe { (*self.slots[head % self.slots.len()].get()).assume_init() };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_order_and_overflow() {
+        let buffer = RingBuffer::new(3);
+        assert!(buffer.push(1));
+        assert!(buffer.push(2));
+        assert!(buffer.push(3));
+        assert!(!buffer.push(4), "Push into a full buffer should fail");
+        assert_eq!(buffer.len(), 3);
+
+        assert_eq!(buffer.pop(), Some(1));
+        assert!(buffer.push(5));
+        assert_eq!(buffer.pop(), Some(2));
+        assert_eq!(buffer.pop(), Some(3));
+        assert_eq!(buffer.pop(), Some(5));
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn test_ring_buffer_across_threads() {
+        let buffer = Arc::new(RingBuffer::new(16));
+        let producer = Arc::clone(&buffer);
+        let handle = thread::spawn(move || {
+            for i in 0..1_000u32 {
+                while !producer.push(i) {
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut expected = 0;
+        while expected < 1_000 {
+            if let Some(value) = buffer.pop() {
+                assert_eq!(value, expected, "Items must arrive in order");
+                expected += 1;
+            }
+        }
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_acquisition_config() {
+        let config = AcquisitionConfig::channel(2).unwrap()
+            .with_gain(PGA::Four)
+            .with_sample_rate(SampleRates::S490Hz)
+            .with_capacity(64);
+        assert_eq!(config.inputs, vec![Mux::Single2]);
+        assert_eq!(config.gain, PGA::Four);
+        assert_eq!(config.rate, SampleRates::S490Hz);
+        assert_eq!(config.capacity, 64);
+
+        assert!(AcquisitionConfig::channel(4).is_err());
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_acquisition_hardware() {
+        let adc = crate::test_device(crate::QwiicADCConfig::default());
+
+        let config = AcquisitionConfig::scan(&[Mux::Single0, Mux::Single1]).with_capacity(256);
+        let started = Instant::now();
+        let mut acquisition = Acquisition::start(adc, config);
+        thread::sleep(Duration::from_millis(100));
+        let samples = acquisition.drain();
+        let elapsed = started.elapsed();
+
+        // Every sample after the first waits for a channel switch to settle
+        let settling = Duration::from_secs_f32(2.2 / 1600.0);
+        let max_samples = (elapsed.as_secs_f32() / settling.as_secs_f32()) as usize + 1;
+        assert!(!samples.is_empty(), "Samples should have been acquired");
+        assert!(samples.len() <= max_samples, "{} samples in {:?}", samples.len(), elapsed);
+        assert_eq!(acquisition.overflows(), 0);
+        for (i, sample) in samples.iter().enumerate() {
+            assert_eq!(sample.seq, i as u64, "No sample may be dropped");
+            assert_eq!(sample.input, [Mux::Single0, Mux::Single1][i % 2], "Inputs alternate");
+        }
+
+        let mut adc = acquisition.stop().expect("Failed to stop");
+        assert!(adc.is_connected(), "Device should be returned after stopping");
+    }
+}
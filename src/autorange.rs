@@ -0,0 +1,205 @@
+//! Automatic gain ranging
+//!
+//! `QwiicADC::read_auto_range` picks the PGA setting for each reading: it starts
+//! at a configured gain, switches to a wider range when the result is near full
+//! scale and to a narrower one when the result only uses a small fraction of it.
+
+use crate::{AdcError, Mux, QwiicADC, SampleRates, PGA};
+
+/// Settings for auto-ranging reads
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoRange {
+    /// Gain used for the first conversion (default: `PGA::Two`)
+    pub start_gain: PGA,
+    /// Narrowest range the reading may switch to (default: `PGA::Sixteen`)
+    pub max_gain: PGA,
+    /// Fraction of full scale at or above which a wider range is used (default: 0.9)
+    pub upper: f32,
+    /// Fraction of full scale below which a narrower range is used (default: 0.3)
+    pub lower: f32,
+    /// Data rate used for every conversion (default: 1600 SPS)
+    pub rate: SampleRates,
+}
+
+impl Default for AutoRange {
+    fn default() -> Self {
+        AutoRange {
+            start_gain: PGA::Two,
+            max_gain: PGA::Sixteen,
+            upper: 0.9,
+            lower: 0.3,
+            rate: SampleRates::S1600Hz,
+        }
+    }
+}
+
+impl AutoRange {
+    /// Set the gain used for the first conversion
+    pub fn with_start_gain(mut self, gain: PGA) -> Self {
+        self.start_gain = gain;
+        self
+    }
+
+    /// Set the narrowest range the reading may switch to
+    pub fn with_max_gain(mut self, gain: PGA) -> Self {
+        self.max_gain = gain;
+        self
+    }
+
+    /// Set the fractions of full scale that trigger a range change
+    ///
+    /// # Arguments
+    /// * `lower` - Switch to a narrower range below this fraction
+    /// * `upper` - Switch to a wider range at or above this fraction
+    pub fn with_thresholds(mut self, lower: f32, upper: f32) -> Self {
+        self.lower = lower;
+        self.upper = upper;
+        self
+    }
+
+    /// Set the data rate
+    pub fn with_sample_rate(mut self, rate: SampleRates) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Decide the gain for the next conversion
+    ///
+    /// Once a reading has moved to a wider range it never narrows again, so the
+    /// search cannot oscillate between two settings.
+    ///
+    /// # Arguments
+    /// * `gain` - Gain used for the last conversion
+    /// * `fraction` - Magnitude of the last conversion as a fraction of full scale
+    /// * `widened` - Whether the reading has already switched to a wider range
+    ///
+    /// # Returns
+    /// The gain to retry with, or `None` if the last conversion should be kept
+    fn next_gain(&self, gain: PGA, fraction: f32, widened: bool) -> Option<PGA> {
+        if fraction >= self.upper {
+            return gain.wider();
+        }
+        if !widened && fraction < self.lower {
+            return gain.narrower().filter(|&next| range_index(next) <= range_index(self.max_gain));
+        }
+        None
+    }
+}
+
+/// Result of an auto-ranging read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoRangeReading {
+    /// Signed conversion result in counts
+    pub counts: i16,
+    /// Conversion result in volts
+    pub volts: f32,
+    /// Gain used for the returned conversion
+    pub gain: PGA,
+    /// The input saturated the converter even at the widest range
+    pub clipped: bool,
+}
+
+/// Position of a gain in `PGA::RANGES`, from widest (0) to narrowest
+fn range_index(gain: PGA) -> usize {
+    PGA::RANGES.iter().position(|&g| g == gain).unwrap_or(2)
+}
+
+impl QwiicADC {
+    /// Read an input, choosing the gain automatically
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential input
+    /// * `range` - Starting gain, gain limit and switching thresholds
+    ///
+    /// # Returns
+    /// The conversion with the gain it was taken at
+    pub fn read_auto_range(&mut self, input: Mux, range: &AutoRange) -> Result<AutoRangeReading, AdcError> {
+        let full_scale = self.full_scale_counts();
+        let mut gain = if range_index(range.start_gain) > range_index(range.max_gain) {
+            range.max_gain
+        } else {
+            range.start_gain
+        };
+        let mut widened = false;
+
+        loop {
+            let raw = self.read_input(input, gain, range.rate)?;
+            let counts = self.raw_to_signed(raw);
+            let fraction = (counts as f32).abs() / full_scale;
+
+            match range.next_gain(gain, fraction, widened) {
+                Some(next) => {
+                    widened |= range_index(next) < range_index(gain);
+                    gain = next;
+                },
+                None => {
+                    let saturated = self.count_clipping(input, raw).is_saturated();
+                    return Ok(AutoRangeReading {
+                        counts,
+                        volts: self.counts_to_volts(counts as f32, gain),
+                        gain,
+                        clipped: saturated && gain.wider().is_none(),
+                    });
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pga_ordering() {
+        assert_eq!(PGA::TwoThirds.wider(), None);
+        assert_eq!(PGA::Two.wider(), Some(PGA::One));
+        assert_eq!(PGA::Two.narrower(), Some(PGA::Four));
+        assert_eq!(PGA::Sixteen.narrower(), None);
+        assert_eq!(PGA::Mask.narrower(), None);
+    }
+
+    #[test]
+    fn test_next_gain_steps() {
+        let range = AutoRange::default();
+
+        // Near full scale moves to a wider range
+        assert_eq!(range.next_gain(PGA::Two, 0.95, false), Some(PGA::One));
+        // Small signals move to a narrower range
+        assert_eq!(range.next_gain(PGA::Two, 0.1, false), Some(PGA::Four));
+        // Results inside the band are kept
+        assert_eq!(range.next_gain(PGA::Two, 0.5, false), None);
+        // Nothing wider than 2/3
+        assert_eq!(range.next_gain(PGA::TwoThirds, 1.0, true), None);
+    }
+
+    #[test]
+    fn test_next_gain_respects_max_gain() {
+        let range = AutoRange::default().with_max_gain(PGA::Four);
+        assert_eq!(range.next_gain(PGA::Two, 0.01, false), Some(PGA::Four));
+        assert_eq!(range.next_gain(PGA::Four, 0.01, false), None);
+    }
+
+    #[test]
+    fn test_next_gain_does_not_oscillate() {
+        // After widening, a small result is accepted rather than narrowing again
+        let range = AutoRange::default().with_thresholds(0.6, 0.9);
+        assert_eq!(range.next_gain(PGA::One, 0.5, true), None);
+        assert_eq!(range.next_gain(PGA::One, 0.5, false), Some(PGA::Two));
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_read_auto_range_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+
+        // Input at mid-scale of the supply, e.g. a divider from VDD
+        let range = AutoRange::default();
+        let reading = adc.read_auto_range(Mux::Single0, &range).expect("Failed to read");
+        assert!(!reading.clipped, "Mid-scale input clipped at {:?}", reading.gain);
+        assert!(range_index(reading.gain) <= range_index(range.max_gain));
+        let fraction = reading.volts.abs() / (reading.gain.full_scale_mv() / 1000.0);
+        assert!(fraction < range.upper, "{} V is too close to full scale of {:?}", reading.volts, reading.gain);
+        assert_eq!(adc.clipping().count(Mux::Single0), 0);
+    }
+}
@@ -0,0 +1,213 @@
+//! Oversampling and averaging
+//!
+//! `QwiicADC::read_averaged` takes several conversions of one input and reports
+//! their mean, spread and extremes. Large sample counts are taken in continuous
+//! mode, small ones as individual single-shot conversions.
+
+use crate::{AdcError, Mux, QwiicADC, SampleRates, PGA};
+
+/// Sample counts at or above this are taken in continuous mode
+const CONTINUOUS_THRESHOLD: usize = 8;
+
+/// Scale factor turning a median absolute deviation into a standard deviation
+/// estimate for normally distributed noise
+const MAD_SCALE: f32 = 1.4826;
+
+/// Statistics of several conversions of one input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AveragedReading {
+    /// Mean of the kept samples in signed counts
+    pub mean: f32,
+    /// Standard deviation of the kept samples in counts
+    pub std_dev: f32,
+    /// Smallest kept sample in signed counts
+    pub min: i16,
+    /// Largest kept sample in signed counts
+    pub max: i16,
+    /// Mean in volts
+    pub volts: f32,
+    /// Standard deviation in volts
+    pub std_dev_volts: f32,
+    /// Number of samples kept
+    pub count: usize,
+    /// Number of samples rejected as outliers
+    pub rejected: usize,
+}
+
+impl QwiicADC {
+    /// Average several conversions of an input at the default gain and rate
+    ///
+    /// Outliers are rejected if enabled in the configuration.
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential input
+    /// * `n` - Number of conversions to take
+    pub fn read_averaged(&mut self, input: Mux, n: usize) -> Result<AveragedReading, AdcError> {
+        self.read_averaged_with(input, PGA::Two, SampleRates::S1600Hz, n)
+    }
+
+    /// Average several conversions of an input with explicit settings
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential input
+    /// * `gain` - PGA gain setting
+    /// * `rate` - Sample rate setting
+    /// * `n` - Number of conversions to take
+    pub fn read_averaged_with(&mut self, input: Mux, gain: PGA, rate: SampleRates, n: usize) -> Result<AveragedReading, AdcError> {
+        let n = n.max(1);
+        let mut samples = Vec::with_capacity(n);
+
+        if n >= CONTINUOUS_THRESHOLD {
+            for sample in self.stream_input(input, gain, rate)?.take(n) {
+                samples.push(sample?.value);
+            }
+        } else {
+            for _ in 0..n {
+                let raw = self.read_input(input, gain, rate)?;
+                self.count_clipping(input, raw);
+                samples.push(raw);
+            }
+        }
+
+        let samples: Vec<i16> = samples.into_iter().map(|raw| self.raw_to_signed(raw)).collect();
+        let summary = summarize(&samples, self.config.outlier_rejection);
+        Ok(AveragedReading {
+            volts: self.counts_to_volts(summary.mean, gain),
+            std_dev_volts: self.counts_to_volts(summary.std_dev, gain),
+            ..summary
+        })
+    }
+
+    /// Average an input over the configured oversampling factor
+    ///
+    /// The fractional `mean` and `volts` keep the extra resolution gained by
+    /// oversampling, unlike `get_single_ended` and `get_differential`, which
+    /// round the mean to a whole conversion code.
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential input
+    pub fn read_oversampled(&mut self, input: Mux) -> Result<AveragedReading, AdcError> {
+        self.read_averaged(input, self.config.oversampling as usize)
+    }
+
+    /// Read an input averaged over the configured oversampling factor, rounded
+    /// to the raw conversion format
+    pub(crate) fn read_oversampled_raw(&mut self, input: Mux) -> Result<u16, AdcError> {
+        let reading = self.read_oversampled(input)?;
+        Ok(self.signed_to_raw(reading.mean.round() as i16))
+    }
+}
+
+/// Compute the statistics of a set of samples, optionally rejecting outliers
+///
+/// Samples further than `rejection` scaled median absolute deviations from the
+/// median are dropped. Volt fields are left at zero.
+fn summarize(samples: &[i16], rejection: Option<f32>) -> AveragedReading {
+    let kept: Vec<i16> = match rejection {
+        Some(deviations) if samples.len() > 2 => {
+            let median = median(samples.iter().map(|&s| s as f32).collect());
+            let mad = median_abs_deviation(samples, median) * MAD_SCALE;
+            samples.iter()
+                .copied()
+                .filter(|&s| mad == 0.0 || (s as f32 - median).abs() <= deviations * mad)
+                .collect()
+        },
+        _ => samples.to_vec(),
+    };
+
+    let count = kept.len();
+    let mean = kept.iter().map(|&s| s as f32).sum::<f32>() / count.max(1) as f32;
+    let variance = kept.iter().map(|&s| (s as f32 - mean).powi(2)).sum::<f32>() / count.max(1) as f32;
+
+    AveragedReading {
+        mean,
+        std_dev: variance.sqrt(),
+        min: kept.iter().copied().min().unwrap_or(0),
+        max: kept.iter().copied().max().unwrap_or(0),
+        volts: 0.0,
+        std_dev_volts: 0.0,
+        count,
+        rejected: samples.len() - count,
+    }
+}
+
+/// Median absolute deviation of samples around `center`
+fn median_abs_deviation(samples: &[i16], center: f32) -> f32 {
+    median(samples.iter().map(|&s| (s as f32 - center).abs()).collect())
+}
+
+/// Median of a set of values
+pub(crate) fn median(mut values: Vec<f32>) -> f32 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len() % 2 == 1 {
+        values[mid]
+    } else {
+        (values[mid - 1] + values[mid]) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_statistics() {
+        let summary = summarize(&[10, 12, 14, 16], None);
+        assert_eq!(summary.mean, 13.0);
+        assert!((summary.std_dev - 5.0f32.sqrt()).abs() < 1e-6);
+        assert_eq!(summary.min, 10);
+        assert_eq!(summary.max, 16);
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.rejected, 0);
+    }
+
+    #[test]
+    fn test_summarize_negative_samples() {
+        let summary = summarize(&[-5, -3, -1], None);
+        assert_eq!(summary.mean, -3.0);
+        assert_eq!(summary.min, -5);
+        assert_eq!(summary.max, -1);
+    }
+
+    #[test]
+    fn test_outlier_rejection() {
+        let samples = [100, 101, 99, 100, 102, 98, 100, 2047];
+        let summary = summarize(&samples, Some(3.0));
+        assert_eq!(summary.rejected, 1, "The spike should be rejected");
+        assert_eq!(summary.max, 102);
+        assert!((summary.mean - 100.0).abs() < 0.01);
+
+        // Without rejection the spike pulls the mean up
+        let summary = summarize(&samples, None);
+        assert_eq!(summary.rejected, 0);
+        assert!(summary.mean > 300.0);
+    }
+
+    #[test]
+    fn test_outlier_rejection_constant_input() {
+        // A noise-free input has zero deviation and nothing is rejected
+        let summary = summarize(&[5, 5, 5, 5], Some(3.0));
+        assert_eq!(summary.rejected, 0);
+        assert_eq!(summary.std_dev, 0.0);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_read_averaged_hardware() {
+        let config = crate::QwiicADCConfig::default().with_outlier_rejection(3.0).with_oversampling(16);
+        let mut adc = crate::test_device(config);
+
+        for n in [4, 64] {
+            let reading = adc.read_averaged(Mux::Single0, n).expect("Failed to read");
+            assert_eq!(reading.count + reading.rejected, n);
+            assert!(reading.count > n / 2, "Most samples should be kept");
+            assert!(reading.min as f32 <= reading.mean && reading.mean <= reading.max as f32);
+            assert!(reading.std_dev <= (reading.max - reading.min) as f32 / 2.0 + 1e-3);
+            assert!((reading.volts - adc.counts_to_volts(reading.mean, PGA::Two)).abs() < 1e-6);
+        }
+
+        let reading = adc.read_oversampled(Mux::Single0).expect("Failed to read");
+        assert_eq!(reading.count + reading.rejected, 16);
+    }
+}
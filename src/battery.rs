@@ -0,0 +1,343 @@
+//! Battery voltage and state-of-charge monitoring
+//!
+//! A [`BatteryMonitor`] reads a battery through a resistor divider, smooths the
+//! pack voltage with an exponential moving average and maps the per-cell
+//! voltage to state of charge with a chemistry curve. Low and critical charge
+//! levels are rules in an [`EventEngine`], so they have hysteresis and can be
+//! observed with callbacks or channels like any other event.
+
+use std::time::Instant;
+
+use crate::filters::{ExponentialMovingAverage, Filter};
+use crate::{AdcError, EventEngine, Mux, QwiicADC, Rule, RuleCondition, SampleRates, Scaling, PGA};
+
+/// Name of the rule active while the charge is low
+pub const LOW_RULE: &str = "battery low";
+/// Name of the rule active while the charge is critical
+pub const CRITICAL_RULE: &str = "battery critical";
+
+/// Resting cell voltage to state of charge (percent) for Li-ion and LiPo cells
+const LI_ION: [(f32, f32); 12] = [
+    (3.00, 0.0), (3.45, 5.0), (3.61, 10.0), (3.67, 20.0), (3.71, 30.0), (3.75, 40.0),
+    (3.79, 50.0), (3.85, 60.0), (3.92, 70.0), (4.00, 80.0), (4.10, 90.0), (4.20, 100.0),
+];
+
+/// Resting cell voltage to state of charge (percent) for LiFePO4 cells
+const LIFEPO4: [(f32, f32); 13] = [
+    (2.50, 0.0), (3.00, 9.0), (3.20, 14.0), (3.22, 17.0), (3.25, 20.0), (3.26, 30.0), (3.27, 40.0),
+    (3.28, 50.0), (3.29, 60.0), (3.30, 70.0), (3.32, 90.0), (3.35, 99.0), (3.40, 100.0),
+];
+
+/// Resting cell voltage to state of charge (percent) for flooded and AGM lead-acid cells
+const LEAD_ACID: [(f32, f32); 11] = [
+    (1.750, 0.0), (1.918, 10.0), (1.943, 20.0), (1.968, 30.0), (1.993, 40.0), (2.017, 50.0),
+    (2.040, 60.0), (2.062, 70.0), (2.083, 80.0), (2.103, 90.0), (2.122, 100.0),
+];
+
+/// Resting cell voltage to state of charge (percent) for NiMH cells
+const NIMH: [(f32, f32); 11] = [
+    (1.00, 0.0), (1.10, 10.0), (1.18, 20.0), (1.20, 30.0), (1.22, 40.0), (1.24, 50.0),
+    (1.26, 60.0), (1.28, 70.0), (1.30, 80.0), (1.33, 90.0), (1.40, 100.0),
+];
+
+/// Battery chemistry, selecting the state-of-charge curve
+#[derive(Debug, Clone, PartialEq)]
+pub enum Chemistry {
+    /// Li-ion or LiPo, 3.0 to 4.2 V per cell
+    LiIon,
+    /// Lithium iron phosphate, 2.5 to 3.4 V per cell
+    LiFePO4,
+    /// Lead-acid, 1.75 to 2.12 V per cell (10.5 to 12.73 V for a 12 V battery)
+    LeadAcid,
+    /// Nickel–metal hydride, 1.0 to 1.4 V per cell
+    NiMH,
+    /// Custom (cell volts, percent) table
+    Custom(Vec<(f32, f32)>),
+}
+
+impl Chemistry {
+    /// (cell volts, percent) points of the state-of-charge curve
+    pub fn curve(&self) -> &[(f32, f32)] {
+        match self {
+            Chemistry::LiIon => &LI_ION,
+            Chemistry::LiFePO4 => &LIFEPO4,
+            Chemistry::LeadAcid => &LEAD_ACID,
+            Chemistry::NiMH => &NIMH,
+            Chemistry::Custom(points) => points,
+        }
+    }
+}
+
+/// Charge level of the battery
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatteryLevel {
+    /// Above the low threshold
+    Normal,
+    /// At or below the low threshold
+    Low,
+    /// At or below the critical threshold
+    Critical,
+}
+
+/// A battery reading
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatteryStatus {
+    /// Smoothed pack voltage
+    pub volts: f32,
+    /// Smoothed voltage per cell
+    pub cell_volts: f32,
+    /// State of charge in percent, between 0 and 100
+    pub state_of_charge: f32,
+    /// Charge level after hysteresis
+    pub level: BatteryLevel,
+}
+
+/// Battery monitor
+pub struct BatteryMonitor {
+    /// Input connected to the divider; private because the event rules watch it
+    input: Mux,
+    /// Pack voltage over the voltage at the input (default: 1)
+    pub divider: f32,
+    /// Number of cells in series (default: 1)
+    pub cells: u32,
+    /// PGA gain setting (default: `PGA::One`)
+    pub gain: PGA,
+    /// Data rate (default: 1600 SPS)
+    pub rate: SampleRates,
+    curve: Scaling,
+    smoothing: ExponentialMovingAverage,
+    events: EventEngine,
+    thresholds: (f32, f32, f32),
+}
+
+impl BatteryMonitor {
+    /// Create a monitor for a single cell connected directly to an input
+    ///
+    /// Low and critical thresholds default to 20% and 5% with 2% hysteresis.
+    ///
+    /// # Returns
+    /// * `Ok(monitor)` - The monitor
+    /// * `Err(AdcError::InvalidCalibration)` if a custom curve has fewer than two distinct voltages
+    pub fn new(input: Mux, chemistry: Chemistry) -> Result<BatteryMonitor, AdcError> {
+        let curve = Scaling::piecewise(chemistry.curve(), "%")
+            .ok_or_else(|| AdcError::InvalidCalibration("state-of-charge curve needs at least two distinct voltages".to_string()))?;
+        Ok(BatteryMonitor {
+            input,
+            divider: 1.0,
+            cells: 1,
+            gain: PGA::One,
+            rate: SampleRates::S1600Hz,
+            curve,
+            smoothing: ExponentialMovingAverage::new(0.2),
+            events: EventEngine::new(),
+            thresholds: (20.0, 5.0, 2.0),
+        }.with_thresholds(20.0, 5.0, 2.0))
+    }
+
+    /// Input connected to the divider
+    pub fn input(&self) -> Mux {
+        self.input
+    }
+
+    /// Move the monitor to another input, rebuilding the event rules for it
+    pub fn with_input(mut self, input: Mux) -> Self {
+        self.input = input;
+        let (low, critical, hysteresis) = self.thresholds;
+        self.with_thresholds(low, critical, hysteresis)
+    }
+
+    /// Set the divider ratio
+    ///
+    /// # Arguments
+    /// * `ratio` - Pack voltage over input voltage, e.g. 4.0 for 30k over 10k
+    pub fn with_divider(mut self, ratio: f32) -> Self {
+        self.divider = ratio;
+        self
+    }
+
+    /// Set the number of cells in series
+    pub fn with_cells(mut self, cells: u32) -> Self {
+        self.cells = cells.max(1);
+        self
+    }
+
+    /// Set the PGA gain setting
+    pub fn with_gain(mut self, gain: PGA) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Set the data rate
+    pub fn with_sample_rate(mut self, rate: SampleRates) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Set the weight of each new reading in the moving average, between 0 and 1
+    pub fn with_smoothing(mut self, alpha: f32) -> Self {
+        self.smoothing = ExponentialMovingAverage::new(alpha);
+        self
+    }
+
+    /// Set the low and critical thresholds, replacing the event rules
+    ///
+    /// Registered callbacks and subscribers are kept.
+    ///
+    /// # Arguments
+    /// * `low` - State of charge in percent at which the battery is low
+    /// * `critical` - State of charge in percent at which the battery is critical
+    /// * `hysteresis` - Recovery above a threshold needed to clear it, in percent
+    pub fn with_thresholds(mut self, low: f32, critical: f32, hysteresis: f32) -> Self {
+        self.thresholds = (low, critical, hysteresis);
+        self.events.clear_rules();
+        self.events.add_rule(Rule::new(LOW_RULE, self.input, RuleCondition::Below(low)).with_hysteresis(hysteresis));
+        self.events.add_rule(Rule::new(CRITICAL_RULE, self.input, RuleCondition::Below(critical)).with_hysteresis(hysteresis));
+        self
+    }
+
+    /// Event engine raising `LOW_RULE` and `CRITICAL_RULE` events, for adding
+    /// callbacks or subscribers
+    pub fn events(&mut self) -> &mut EventEngine {
+        &mut self.events
+    }
+
+    /// Update the monitor with the voltage at the input
+    pub fn update(&mut self, input_volts: f32, timestamp: Instant) -> BatteryStatus {
+        let volts = self.smoothing.process(input_volts * self.divider);
+        let cell_volts = volts / self.cells as f32;
+        let state_of_charge = self.curve.apply(cell_volts).clamp(0.0, 100.0);
+        self.events.evaluate(self.input, state_of_charge, timestamp);
+
+        let level = if self.events.is_active(CRITICAL_RULE) {
+            BatteryLevel::Critical
+        } else if self.events.is_active(LOW_RULE) {
+            BatteryLevel::Low
+        } else {
+            BatteryLevel::Normal
+        };
+        BatteryStatus { volts, cell_volts, state_of_charge, level }
+    }
+
+    /// Restart smoothing, e.g. after the battery was replaced
+    pub fn reset(&mut self) {
+        self.smoothing.reset();
+    }
+}
+
+impl QwiicADC {
+    /// Read a battery and update its monitor
+    ///
+    /// Configured calibration is applied to the input voltage.
+    pub fn read_battery(&mut self, monitor: &mut BatteryMonitor) -> Result<BatteryStatus, AdcError> {
+        let volts = self.read_volts(monitor.input(), monitor.gain, monitor.rate)?;
+        Ok(monitor.update(volts, Instant::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EventKind;
+
+    #[test]
+    fn test_state_of_charge_curves() {
+        let now = Instant::now();
+        let mut monitor = BatteryMonitor::new(Mux::Single0, Chemistry::LiIon).unwrap();
+        assert_eq!(monitor.update(4.2, now).state_of_charge, 100.0);
+
+        let mut monitor = BatteryMonitor::new(Mux::Single0, Chemistry::LiIon).unwrap();
+        assert!((monitor.update(3.77, now).state_of_charge - 45.0).abs() < 0.01);
+
+        // 12 V lead-acid at 12.10 V through a 4:1 divider is half charged
+        let mut monitor = BatteryMonitor::new(Mux::Single1, Chemistry::LeadAcid).unwrap()
+            .with_divider(4.0)
+            .with_cells(6)
+            .with_gain(PGA::Two)
+            .with_sample_rate(SampleRates::S128Hz);
+        assert_eq!((monitor.gain, monitor.rate), (PGA::Two, SampleRates::S128Hz));
+        let status = monitor.update(12.10 / 4.0, now);
+        assert!((status.cell_volts - 2.0167).abs() < 1e-3);
+        assert!((status.state_of_charge - 50.0).abs() < 1.0);
+
+        // Clamped outside the curve
+        let mut monitor = BatteryMonitor::new(Mux::Single0, Chemistry::NiMH).unwrap();
+        assert_eq!(monitor.update(0.5, now).state_of_charge, 0.0);
+    }
+
+    #[test]
+    fn test_custom_curve() {
+        let now = Instant::now();
+        let mut monitor = BatteryMonitor::new(Mux::Single0, Chemistry::Custom(vec![(1.0, 0.0), (2.0, 100.0)])).unwrap();
+        assert_eq!(monitor.update(1.25, now).state_of_charge, 25.0);
+
+        assert!(BatteryMonitor::new(Mux::Single0, Chemistry::Custom(vec![(1.0, 0.0)])).is_err());
+    }
+
+    #[test]
+    fn test_change_input() {
+        let now = Instant::now();
+        let mut monitor = BatteryMonitor::new(Mux::Single0, Chemistry::Custom(vec![(0.0, 0.0), (1.0, 100.0)]))
+            .unwrap()
+            .with_smoothing(1.0)
+            .with_thresholds(30.0, 10.0, 2.0)
+            .with_input(Mux::Single2);
+        assert_eq!(monitor.input(), Mux::Single2);
+        let events = monitor.events().subscribe();
+
+        // The rules follow the input and keep their thresholds
+        assert_eq!(monitor.update(0.25, now).level, BatteryLevel::Low);
+        let event = events.try_recv().expect("Low event on the new input");
+        assert_eq!((event.rule.as_str(), event.input, event.kind), (LOW_RULE, Mux::Single2, EventKind::Triggered));
+    }
+
+    #[test]
+    fn test_smoothing() {
+        let now = Instant::now();
+        let mut monitor = BatteryMonitor::new(Mux::Single0, Chemistry::LiIon).unwrap().with_smoothing(0.5);
+        monitor.update(4.0, now);
+        assert_eq!(monitor.update(3.8, now).volts, 3.9);
+
+        monitor.reset();
+        assert_eq!(monitor.update(3.8, now).volts, 3.8);
+    }
+
+    #[test]
+    fn test_low_and_critical_events() {
+        let start = Instant::now();
+        let mut monitor = BatteryMonitor::new(Mux::Single0, Chemistry::Custom(vec![(0.0, 0.0), (1.0, 100.0)]))
+            .unwrap()
+            .with_smoothing(1.0)
+            .with_thresholds(20.0, 5.0, 2.0);
+        let events = monitor.events().subscribe();
+
+        let levels: Vec<BatteryLevel> = [0.5, 0.19, 0.04, 0.06, 0.08, 0.21, 0.23]
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| monitor.update(v, start + std::time::Duration::from_secs(i as u64)).level)
+            .collect();
+        assert_eq!(levels, vec![
+            BatteryLevel::Normal, BatteryLevel::Low, BatteryLevel::Critical,
+            BatteryLevel::Critical, BatteryLevel::Low, BatteryLevel::Low, BatteryLevel::Normal,
+        ]);
+
+        let received: Vec<(String, EventKind)> = events.try_iter().map(|e| (e.rule, e.kind)).collect();
+        assert_eq!(received, vec![
+            (LOW_RULE.to_string(), EventKind::Triggered),
+            (CRITICAL_RULE.to_string(), EventKind::Triggered),
+            (CRITICAL_RULE.to_string(), EventKind::Cleared),
+            (LOW_RULE.to_string(), EventKind::Cleared),
+        ]);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_battery_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+
+        let mut monitor = BatteryMonitor::new(Mux::Single0, Chemistry::LiIon).unwrap().with_divider(2.0);
+        let status = adc.read_battery(&mut monitor).expect("Failed to read");
+        assert!(status.volts > 0.0, "No battery connected");
+        assert!((0.0..=100.0).contains(&status.state_of_charge), "State of charge {}%", status.state_of_charge);
+        assert!((status.cell_volts - status.volts / monitor.cells as f32).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,432 @@
+//! Per-input offset and gain calibration
+//!
+//! A [`Calibration`] holds an offset and gain correction for one input at each
+//! PGA setting it has been calibrated for. Corrections are derived by measuring
+//! the input shorted and then connected to a known reference. A
+//! [`CalibrationSet`] groups the calibrations of all inputs; assigned to the
+//! configuration, it corrects `read_volts` and scan results, and also holds
+//! pH and ORP probe calibrations. Sets are saved as plain text, one correction
+//! per line.
+
+use std::fs;
+use std::path::Path;
+
+use crate::{AdcError, Mux, PhCalibration, QwiicADC, SampleRates, PGA};
+
+/// First line of a saved calibration set
+const HEADER: &str = "# qwiic-adc calibration v1";
+
+/// Offset and gain correction for one input at one PGA setting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Correction {
+    /// Reading with the input shorted, in volts
+    pub offset: f32,
+    /// Factor applied to the offset-corrected reading
+    pub gain: f32,
+}
+
+impl Correction {
+    /// Correction that leaves readings unchanged
+    pub const IDENTITY: Correction = Correction { offset: 0.0, gain: 1.0 };
+
+    /// Derive a correction from calibration measurements
+    ///
+    /// # Arguments
+    /// * `zero` - Reading with the input shorted, in volts
+    /// * `measured` - Reading with the reference connected, in volts
+    /// * `reference` - Actual reference voltage
+    ///
+    /// # Returns
+    /// The correction, or `None` if the two readings are equal
+    pub fn from_measurements(zero: f32, measured: f32, reference: f32) -> Option<Correction> {
+        let span = measured - zero;
+        if span == 0.0 || !span.is_finite() {
+            return None;
+        }
+        Some(Correction { offset: zero, gain: reference / span })
+    }
+
+    /// Apply the correction to a reading in volts
+    pub fn apply(&self, volts: f32) -> f32 {
+        (volts - self.offset) * self.gain
+    }
+}
+
+impl Default for Correction {
+    fn default() -> Self {
+        Correction::IDENTITY
+    }
+}
+
+/// Corrections for one input, per PGA setting
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calibration {
+    input: Mux,
+    corrections: Vec<(PGA, Correction)>,
+}
+
+impl Calibration {
+    /// Create an uncalibrated entry for an input
+    pub fn new(input: Mux) -> Calibration {
+        Calibration { input, corrections: Vec::new() }
+    }
+
+    /// Set the correction for a gain setting
+    pub fn with_correction(mut self, gain: PGA, correction: Correction) -> Self {
+        self.set_correction(gain, correction);
+        self
+    }
+
+    /// Set the correction for a gain setting, replacing any previous one
+    pub fn set_correction(&mut self, gain: PGA, correction: Correction) {
+        match self.corrections.iter_mut().find(|(g, _)| *g == gain) {
+            Some((_, existing)) => *existing = correction,
+            None => self.corrections.push((gain, correction)),
+        }
+    }
+
+    /// Input the calibration applies to
+    pub fn input(&self) -> Mux {
+        self.input
+    }
+
+    /// Get the correction for a gain setting, if calibrated
+    pub fn correction(&self, gain: PGA) -> Option<Correction> {
+        self.corrections.iter().find(|(g, _)| *g == gain).map(|&(_, c)| c)
+    }
+
+    /// Correct a reading taken at `gain`, leaving it unchanged if that gain is not calibrated
+    pub fn apply(&self, gain: PGA, volts: f32) -> f32 {
+        self.correction(gain).unwrap_or_default().apply(volts)
+    }
+}
+
+/// Calibrations of several inputs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CalibrationSet {
+    calibrations: Vec<Calibration>,
+    ph: Vec<(Mux, PhCalibration)>,
+    orp: Vec<(Mux, f32)>,
+}
+
+impl CalibrationSet {
+    /// Create an empty set
+    pub fn new() -> CalibrationSet {
+        CalibrationSet::default()
+    }
+
+    /// Add a calibration, replacing any existing one for the same input
+    pub fn with_calibration(mut self, calibration: Calibration) -> Self {
+        self.insert(calibration);
+        self
+    }
+
+    /// Add a calibration, replacing any existing one for the same input
+    pub fn insert(&mut self, calibration: Calibration) {
+        self.calibrations.retain(|c| c.input != calibration.input);
+        self.calibrations.push(calibration);
+    }
+
+    /// Get the calibration of an input
+    pub fn get(&self, input: Mux) -> Option<&Calibration> {
+        self.calibrations.iter().find(|c| c.input == input)
+    }
+
+    /// Get the calibration of an input, creating an empty one if needed
+    pub fn entry(&mut self, input: Mux) -> &mut Calibration {
+        match self.calibrations.iter().position(|c| c.input == input) {
+            Some(index) => &mut self.calibrations[index],
+            None => {
+                self.calibrations.push(Calibration::new(input));
+                self.calibrations.last_mut().expect("just pushed")
+            },
+        }
+    }
+
+    /// Set the pH probe calibration of an input, replacing any previous one
+    pub fn set_ph(&mut self, input: Mux, calibration: PhCalibration) {
+        self.ph.retain(|(i, _)| *i != input);
+        self.ph.push((input, calibration));
+    }
+
+    /// Get the pH probe calibration of an input
+    pub fn ph(&self, input: Mux) -> Option<PhCalibration> {
+        self.ph.iter().find(|(i, _)| *i == input).map(|&(_, calibration)| calibration)
+    }
+
+    /// Set the ORP probe offset of an input in volts, replacing any previous one
+    pub fn set_orp_offset(&mut self, input: Mux, offset: f32) {
+        self.orp.retain(|(i, _)| *i != input);
+        self.orp.push((input, offset));
+    }
+
+    /// Get the ORP probe offset of an input in volts
+    pub fn orp_offset(&self, input: Mux) -> Option<f32> {
+        self.orp.iter().find(|(i, _)| *i == input).map(|&(_, offset)| offset)
+    }
+
+    /// Check if no input is calibrated
+    pub fn is_empty(&self) -> bool {
+        self.calibrations.is_empty() && self.ph.is_empty() && self.orp.is_empty()
+    }
+
+    /// Correct a reading of an input taken at `gain`
+    pub fn apply(&self, input: Mux, gain: PGA, volts: f32) -> f32 {
+        match self.get(input) {
+            Some(calibration) => calibration.apply(gain, volts),
+            None => volts,
+        }
+    }
+
+    /// Serialise the set as text
+    ///
+    /// Each correction is a line `<input> <gain> <offset> <gain factor>`, e.g.
+    /// `Single0 Two 0.0012 1.0031`. pH probes are lines `ph <input> <offset>
+    /// <slope>` and ORP probes `orp <input> <offset>`. Blank lines and lines
+    /// starting with `#` are ignored when parsing.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n", HEADER);
+        for calibration in &self.calibrations {
+            for (gain, correction) in &calibration.corrections {
+                text.push_str(&format!("{:?} {:?} {} {}\n",
+                    calibration.input, gain, correction.offset, correction.gain));
+            }
+        }
+        for (input, calibration) in &self.ph {
+            text.push_str(&format!("ph {:?} {} {}\n", input, calibration.offset, calibration.slope));
+        }
+        for (input, offset) in &self.orp {
+            text.push_str(&format!("orp {:?} {}\n", input, offset));
+        }
+        text
+    }
+
+    /// Parse a set written by `to_text`
+    ///
+    /// # Returns
+    /// * `Ok(set)` - The parsed set
+    /// * `Err(AdcError::InvalidCalibration)` if a line cannot be parsed
+    pub fn from_text(text: &str) -> Result<CalibrationSet, AdcError> {
+        let mut set = CalibrationSet::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: &str| AdcError::InvalidCalibration(format!("line {}: {}", number + 1, reason));
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[0] {
+                "ph" if fields.len() == 4 => {
+                    let input = parse_mux(fields[1]).ok_or_else(|| invalid("unknown input"))?;
+                    let offset = fields[2].parse().map_err(|_| invalid("invalid offset"))?;
+                    let slope = fields[3].parse().map_err(|_| invalid("invalid slope"))?;
+                    set.set_ph(input, PhCalibration { offset, slope });
+                    continue;
+                },
+                "ph" => return Err(invalid("expected input, offset and slope")),
+                "orp" if fields.len() == 3 => {
+                    let input = parse_mux(fields[1]).ok_or_else(|| invalid("unknown input"))?;
+                    let offset = fields[2].parse().map_err(|_| invalid("invalid offset"))?;
+                    set.set_orp_offset(input, offset);
+                    continue;
+                },
+                "orp" => return Err(invalid("expected input and offset")),
+                _ => {},
+            }
+            if fields.len() != 4 {
+                return Err(invalid("expected input, gain, offset and gain factor"));
+            }
+            let input = parse_mux(fields[0]).ok_or_else(|| invalid("unknown input"))?;
+            let gain = parse_gain(fields[1]).ok_or_else(|| invalid("unknown gain"))?;
+            let offset = fields[2].parse().map_err(|_| invalid("invalid offset"))?;
+            let factor = fields[3].parse().map_err(|_| invalid("invalid gain factor"))?;
+            set.entry(input).set_correction(gain, Correction { offset, gain: factor });
+        }
+        Ok(set)
+    }
+
+    /// Save the set to a text file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), AdcError> {
+        fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    /// Load a set saved with `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<CalibrationSet, AdcError> {
+        CalibrationSet::from_text(&fs::read_to_string(path)?)
+    }
+}
+
+/// Parse the name of an input as written by `to_text`
+pub(crate) fn parse_mux(name: &str) -> Option<Mux> {
+    (0..8u16)
+        .filter_map(|i| Mux::from_bits(i << 12))
+        .find(|input| format!("{:?}", input) == name)
+}
+
+/// Parse the name of a gain setting as written by `to_text`
+pub(crate) fn parse_gain(name: &str) -> Option<PGA> {
+    PGA::RANGES.iter().copied().find(|gain| format!("{:?}", gain) == name)
+}
+
+/// Step of the calibration procedure the input must be prepared for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CalibrationStep {
+    /// Short the input (or connect both differential inputs together)
+    Short,
+    /// Connect the input to the reference voltage
+    Reference(f32),
+}
+
+impl QwiicADC {
+    /// Calibrate an input at one gain setting
+    ///
+    /// `prepare` is called before each measurement and must return once the
+    /// input is connected as the step requires, e.g. after prompting the user or
+    /// switching a relay. The correction is stored in the configured calibration
+    /// set, replacing any previous correction for the input and gain.
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential input
+    /// * `gain` - PGA gain setting to calibrate
+    /// * `reference` - Reference voltage, within the range of `gain`
+    /// * `samples` - Number of conversions averaged per measurement
+    /// * `prepare` - Called with each step before it is measured
+    ///
+    /// # Returns
+    /// * `Ok(correction)` - The new correction
+    /// * `Err(AdcError::InvalidCalibration)` if the reference reads the same as the short
+    pub fn calibrate<F>(&mut self, input: Mux, gain: PGA, reference: f32, samples: usize, mut prepare: F) -> Result<Correction, AdcError>
+    where
+        F: FnMut(CalibrationStep),
+    {
+        prepare(CalibrationStep::Short);
+        let zero = self.read_averaged_with(input, gain, SampleRates::S1600Hz, samples)?.volts;
+
+        prepare(CalibrationStep::Reference(reference));
+        let measured = self.read_averaged_with(input, gain, SampleRates::S1600Hz, samples)?.volts;
+
+        let correction = Correction::from_measurements(zero, measured, reference)
+            .ok_or_else(|| AdcError::InvalidCalibration(format!("{:?} reads the same shorted and at the reference", input)))?;
+        self.config.calibration.entry(input).set_correction(gain, correction);
+        Ok(correction)
+    }
+
+    /// Get the calibration set applied to readings
+    pub fn calibration(&self) -> &CalibrationSet {
+        &self.config.calibration
+    }
+
+    /// Replace the calibration set applied to readings
+    pub fn set_calibration(&mut self, calibration: CalibrationSet) {
+        self.config.calibration = calibration;
+    }
+
+    /// Read an input in single-shot mode and return calibrated volts
+    ///
+    /// A saturated conversion is counted in `clipping`; use `read` to check it.
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential input
+    /// * `gain` - PGA gain setting
+    /// * `rate` - Sample rate setting
+    pub fn read_volts(&mut self, input: Mux, gain: PGA, rate: SampleRates) -> Result<f32, AdcError> {
+        Ok(self.read(input, gain, rate)?.volts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_correction_from_measurements() {
+        // 2 mV offset, 1% low gain
+        let correction = Correction::from_measurements(0.002, 0.002 + 0.99, 1.0).unwrap();
+        assert!((correction.apply(0.002)).abs() < 1e-6);
+        assert!((correction.apply(0.992) - 1.0).abs() < 1e-6);
+        assert!((correction.apply(0.497) - 0.5).abs() < 1e-6);
+
+        assert_eq!(Correction::from_measurements(0.5, 0.5, 1.0), None);
+    }
+
+    #[test]
+    fn test_apply_per_gain() {
+        let set = CalibrationSet::new()
+            .with_calibration(Calibration::new(Mux::Single0)
+                .with_correction(PGA::Two, Correction { offset: 0.01, gain: 2.0 }));
+
+        assert_eq!(set.apply(Mux::Single0, PGA::Two, 0.51), 1.0);
+        assert_eq!(set.apply(Mux::Single0, PGA::One, 0.51), 0.51, "Other gains are uncorrected");
+        assert_eq!(set.apply(Mux::Single1, PGA::Two, 0.51), 0.51, "Other inputs are uncorrected");
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let mut set = CalibrationSet::new();
+        set.entry(Mux::Single0).set_correction(PGA::Two, Correction { offset: 0.0012, gain: 1.0031 });
+        set.entry(Mux::Single0).set_correction(PGA::Sixteen, Correction { offset: -0.0001, gain: 0.998 });
+        set.entry(Mux::DiffP2N3).set_correction(PGA::TwoThirds, Correction { offset: 0.0, gain: 1.0 });
+        set.set_ph(Mux::Single1, PhCalibration { offset: 2.51, slope: -0.1712 });
+        set.set_orp_offset(Mux::Single2, -0.004);
+
+        let text = set.to_text();
+        assert!(text.starts_with(HEADER));
+        assert!(text.contains("Single0 Two 0.0012 1.0031"));
+        assert!(text.contains("ph Single1 2.51 -0.1712"));
+        assert!(text.contains("orp Single2 -0.004"));
+        assert_eq!(CalibrationSet::from_text(&text).unwrap(), set);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = CalibrationSet::from_text("Single0 Two 0.1\n").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid calibration data: line 1: expected input, gain, offset and gain factor");
+
+        let err = CalibrationSet::from_text("# comment\n\nSingle9 Two 0.1 1.0\n").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid calibration data: line 3: unknown input");
+
+        assert!(CalibrationSet::from_text("Single0 Three 0.1 1.0").is_err());
+        assert!(CalibrationSet::from_text("Single0 Two zero 1.0").is_err());
+        assert!(CalibrationSet::from_text("ph Single0 2.5").is_err());
+        assert!(CalibrationSet::from_text("orp Single4 0.0").is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("qwiic-adc-calibration-{}.txt", std::process::id()));
+        let set = CalibrationSet::new()
+            .with_calibration(Calibration::new(Mux::DiffP0N1)
+                .with_correction(PGA::Eight, Correction { offset: 0.00025, gain: 1.002 }));
+
+        set.save(&path).unwrap();
+        let loaded = CalibrationSet::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, set);
+
+        assert!(matches!(CalibrationSet::load(&path), Err(AdcError::Io(_))));
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_calibrate_hardware() {
+        // The interactive procedure is `cargo run --example calibrate`; here the
+        // correction comes from the configuration
+        let correction = Correction { offset: 0.010, gain: 1.020 };
+        let calibration = CalibrationSet::new()
+            .with_calibration(Calibration::new(Mux::Single0).with_correction(PGA::Two, correction));
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default().with_calibration(calibration));
+        assert_eq!(adc.calibration().get(Mux::Single0).and_then(|c| c.correction(PGA::Two)), Some(correction));
+
+        let uncorrected = adc.read_averaged_with(Mux::Single0, PGA::Two, SampleRates::S1600Hz, 16)
+            .expect("Failed to read").volts;
+        let volts = adc.read_volts(Mux::Single0, PGA::Two, SampleRates::S1600Hz).expect("Failed to read");
+        assert!((volts - correction.apply(uncorrected)).abs() < 0.005,
+            "Corrected {} V, expected {} V", volts, correction.apply(uncorrected));
+
+        // Other gains are not calibrated and read uncorrected
+        let volts = adc.read_volts(Mux::Single0, PGA::One, SampleRates::S1600Hz).expect("Failed to read");
+        assert!((volts - uncorrected).abs() < 0.01);
+    }
+}
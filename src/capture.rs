@@ -0,0 +1,332 @@
+//! Oscilloscope-style triggered capture
+//!
+//! A [`Trigger`] watches samples from a continuous-mode stream, keeps a
+//! pre-trigger history and returns a fixed-length [`Capture`] once its
+//! condition is met. `Stream::capture` feeds a trigger from a stream until a
+//! capture is complete.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::{AdcError, Sample, Stream};
+
+/// Condition that fires a trigger
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerCondition {
+    /// The input rises through the level in volts
+    Rising(f32),
+    /// The input falls through the level in volts
+    Falling(f32),
+    /// The input leaves the window between `low` and `high` volts
+    WindowExit {
+        /// Lower edge of the window in volts
+        low: f32,
+        /// Upper edge of the window in volts
+        high: f32,
+    },
+}
+
+impl TriggerCondition {
+    /// Check if the step from `previous` to `current` fires the trigger
+    fn fires(self, previous: f32, current: f32) -> bool {
+        match self {
+            TriggerCondition::Rising(level) => previous < level && current >= level,
+            TriggerCondition::Falling(level) => previous > level && current <= level,
+            TriggerCondition::WindowExit { low, high } => {
+                let inside = |v: f32| v >= low && v <= high;
+                inside(previous) && !inside(current)
+            },
+        }
+    }
+}
+
+/// When the trigger re-arms and whether it waits for its condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerMode {
+    /// Capture once, then stay disarmed until `Trigger::arm` is called
+    Single,
+    /// Re-arm after every capture and wait indefinitely for the condition
+    Normal,
+    /// Like `Normal`, but return an untriggered capture if the condition is
+    /// not met within the timeout
+    Auto(Duration),
+}
+
+/// A completed capture
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    /// Captured samples in order
+    pub samples: Vec<Sample>,
+    /// Index in `samples` of the sample that fired the trigger, or `None` for
+    /// an untriggered capture in auto mode
+    pub trigger_index: Option<usize>,
+}
+
+impl Capture {
+    /// Check if the capture was started by the trigger condition
+    pub fn triggered(&self) -> bool {
+        self.trigger_index.is_some()
+    }
+
+    /// The sample that fired the trigger
+    pub fn trigger_sample(&self) -> Option<&Sample> {
+        self.samples.get(self.trigger_index?)
+    }
+
+    /// Time of each sample relative to the trigger (or the first sample), in seconds
+    pub fn relative_times(&self) -> Vec<f32> {
+        let origin = self.trigger_sample().or(self.samples.first()).map(|s| s.timestamp);
+        self.samples.iter()
+            .map(|sample| match origin {
+                Some(origin) if sample.timestamp >= origin => (sample.timestamp - origin).as_secs_f32(),
+                Some(origin) => -(origin - sample.timestamp).as_secs_f32(),
+                None => 0.0,
+            })
+            .collect()
+    }
+}
+
+/// Trigger state machine
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    condition: TriggerCondition,
+    mode: TriggerMode,
+    pre_trigger: usize,
+    length: usize,
+    armed: bool,
+    armed_at: Option<Instant>,
+    previous: Option<f32>,
+    history: VecDeque<Sample>,
+    capture: Option<(Vec<Sample>, usize)>,
+}
+
+impl Trigger {
+    /// Create an armed trigger
+    ///
+    /// # Arguments
+    /// * `condition` - Condition that fires the trigger
+    /// * `mode` - Single, normal or auto re-arming
+    /// * `length` - Total number of samples per capture
+    /// * `pre_trigger` - Number of samples kept from before the trigger, at most `length - 1`
+    pub fn new(condition: TriggerCondition, mode: TriggerMode, length: usize, pre_trigger: usize) -> Trigger {
+        let length = length.max(1);
+        Trigger {
+            condition,
+            mode,
+            pre_trigger: pre_trigger.min(length - 1),
+            length,
+            armed: true,
+            armed_at: None,
+            previous: None,
+            history: VecDeque::with_capacity(length),
+            capture: None,
+        }
+    }
+
+    /// Check if the trigger is waiting for its condition or completing a capture
+    pub fn is_armed(&self) -> bool {
+        self.armed
+    }
+
+    /// Arm the trigger, discarding any capture in progress
+    pub fn arm(&mut self) {
+        self.armed = true;
+        self.armed_at = None;
+        self.previous = None;
+        self.history.clear();
+        self.capture = None;
+    }
+
+    /// Feed the next sample to the trigger
+    ///
+    /// # Returns
+    /// The capture, once it is complete
+    pub fn push(&mut self, sample: Sample) -> Option<Capture> {
+        if !self.armed {
+            return None;
+        }
+
+        if let Some((samples, trigger_index)) = &mut self.capture {
+            samples.push(sample);
+            if samples.len() < self.length {
+                return None;
+            }
+            let trigger_index = *trigger_index;
+            let (samples, _) = self.capture.take()?;
+            return Some(self.complete(samples, Some(trigger_index)));
+        }
+
+        let previous = self.previous.replace(sample.volts);
+        if previous.is_some_and(|previous| self.condition.fires(previous, sample.volts)) {
+            let skip = self.history.len().saturating_sub(self.pre_trigger);
+            let mut samples: Vec<Sample> = self.history.drain(..).skip(skip).collect();
+            let trigger_index = samples.len();
+            samples.push(sample);
+            if samples.len() >= self.length {
+                return Some(self.complete(samples, Some(trigger_index)));
+            }
+            self.capture = Some((samples, trigger_index));
+            return None;
+        }
+
+        if self.history.len() == self.length {
+            self.history.pop_front();
+        }
+        self.history.push_back(sample);
+
+        let armed_at = *self.armed_at.get_or_insert(sample.timestamp);
+        if let TriggerMode::Auto(timeout) = self.mode {
+            if self.history.len() == self.length && sample.timestamp.saturating_duration_since(armed_at) >= timeout {
+                let samples = self.history.drain(..).collect();
+                return Some(self.complete(samples, None));
+            }
+        }
+        None
+    }
+
+    /// Finish a capture and re-arm or disarm according to the mode
+    fn complete(&mut self, samples: Vec<Sample>, trigger_index: Option<usize>) -> Capture {
+        self.arm();
+        if self.mode == TriggerMode::Single {
+            self.armed = false;
+        }
+        Capture { samples, trigger_index }
+    }
+}
+
+impl Stream<'_> {
+    /// Read samples until the trigger completes a capture
+    ///
+    /// # Returns
+    /// * `Ok(Some(capture))` - The completed capture
+    /// * `Ok(None)` - The trigger is disarmed (single mode after a capture)
+    pub fn capture(&mut self, trigger: &mut Trigger) -> Result<Option<Capture>, AdcError> {
+        while trigger.is_armed() {
+            let sample = self.next().expect("continuous-mode stream never ends")?;
+            if let Some(capture) = trigger.push(sample) {
+                return Ok(Some(capture));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Mux;
+
+    /// Samples with the given volts, 1 ms apart
+    fn samples(volts: &[f32]) -> Vec<Sample> {
+        let start = Instant::now();
+        volts.iter()
+            .enumerate()
+            .map(|(i, &volts)| Sample {
+                input: Mux::Single0,
+                value: 0,
+                volts,
+                timestamp: start + Duration::from_millis(i as u64),
+                seq: i as u64,
+            })
+            .collect()
+    }
+
+    fn run(trigger: &mut Trigger, volts: &[f32]) -> Vec<Capture> {
+        samples(volts).into_iter().filter_map(|sample| trigger.push(sample)).collect()
+    }
+
+    #[test]
+    fn test_rising_edge_with_pre_trigger() {
+        let mut trigger = Trigger::new(TriggerCondition::Rising(1.0), TriggerMode::Single, 5, 2);
+        let captures = run(&mut trigger, &[0.0, 0.1, 0.2, 0.3, 1.5, 1.6, 1.7, 1.8, 0.0, 2.0]);
+
+        assert_eq!(captures.len(), 1, "Single mode captures once");
+        let capture = &captures[0];
+        let volts: Vec<f32> = capture.samples.iter().map(|s| s.volts).collect();
+        assert_eq!(volts, vec![0.2, 0.3, 1.5, 1.6, 1.7]);
+        assert_eq!(capture.trigger_index, Some(2));
+        assert_eq!(capture.trigger_sample().unwrap().volts, 1.5);
+        assert!(!trigger.is_armed());
+
+        let times = capture.relative_times();
+        assert!((times[0] + 0.002).abs() < 1e-6 && times[2] == 0.0);
+    }
+
+    #[test]
+    fn test_falling_edge_normal_mode_rearms() {
+        let mut trigger = Trigger::new(TriggerCondition::Falling(1.0), TriggerMode::Normal, 2, 0);
+        let captures = run(&mut trigger, &[2.0, 0.5, 0.4, 2.0, 0.5, 0.3]);
+
+        assert_eq!(captures.len(), 2);
+        assert_eq!(captures[0].samples[0].volts, 0.5);
+        assert_eq!(captures[1].samples[0].seq, 4);
+        assert!(trigger.is_armed());
+    }
+
+    #[test]
+    fn test_window_exit() {
+        let mut trigger = Trigger::new(
+            TriggerCondition::WindowExit { low: -1.0, high: 1.0 },
+            TriggerMode::Normal, 1, 0);
+        let captures = run(&mut trigger, &[0.0, 0.5, -0.5, -1.5, 0.0, 1.2]);
+
+        let volts: Vec<f32> = captures.iter().map(|c| c.samples[0].volts).collect();
+        assert_eq!(volts, vec![-1.5, 1.2]);
+    }
+
+    #[test]
+    fn test_short_pre_trigger_history() {
+        // The trigger fires before the requested history is available
+        let mut trigger = Trigger::new(TriggerCondition::Rising(1.0), TriggerMode::Single, 4, 3);
+        let captures = run(&mut trigger, &[0.0, 2.0, 2.0, 2.0]);
+        assert_eq!(captures[0].trigger_index, Some(1));
+        assert_eq!(captures[0].samples.len(), 4);
+    }
+
+    #[test]
+    fn test_auto_mode_times_out() {
+        let mut trigger = Trigger::new(TriggerCondition::Rising(5.0), TriggerMode::Auto(Duration::from_millis(10)), 4, 1);
+        let captures = run(&mut trigger, &[0.0; 30]);
+
+        assert!(!captures.is_empty(), "Auto mode should free-run");
+        assert!(!captures[0].triggered());
+        assert_eq!(captures[0].samples.len(), 4);
+        assert_eq!(captures[0].samples[3].seq, 10);
+    }
+
+    #[test]
+    fn test_rearm_after_single() {
+        let mut trigger = Trigger::new(TriggerCondition::Rising(1.0), TriggerMode::Single, 1, 0);
+        assert_eq!(run(&mut trigger, &[0.0, 2.0, 0.0, 2.0]).len(), 1);
+        trigger.arm();
+        assert_eq!(run(&mut trigger, &[0.0, 2.0]).len(), 1);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_capture_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+        let mut stream = adc.stream(0, crate::SampleRates::S1600Hz).expect("Failed to start stream");
+
+        // Beyond the ±2.048 V range the condition never fires, so auto mode times out
+        let mut trigger = Trigger::new(TriggerCondition::Rising(3.0),
+            TriggerMode::Auto(Duration::from_millis(200)), 256, 64);
+        let capture = stream.capture(&mut trigger).expect("Failed to capture").unwrap();
+        assert!(!capture.triggered(), "Untriggered capture after the auto timeout");
+        assert_eq!(capture.samples.len(), 256);
+        assert!(trigger.is_armed(), "Auto mode re-arms");
+
+        let mut trigger = Trigger::new(TriggerCondition::Rising(1.0),
+            TriggerMode::Auto(Duration::from_millis(500)), 256, 64);
+        let capture = stream.capture(&mut trigger).expect("Failed to capture").unwrap();
+        assert_eq!(capture.samples.len(), 256);
+        for pair in capture.samples.windows(2) {
+            assert!(pair[1].seq > pair[0].seq, "Samples are in order");
+        }
+        if capture.triggered() {
+            let index = capture.trigger_index.unwrap();
+            assert!((1..=64).contains(&index), "At most the pre-trigger history precedes the trigger");
+            assert!(capture.samples[index - 1].volts < 1.0 && capture.samples[index].volts >= 1.0);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! 4–20 mA current loops
+//!
+//! A [`CurrentLoop`] reads the voltage across a shunt resistor, converts it to
+//! loop current and scales 4–20 mA onto the transmitter's engineering range.
+//! Currents outside the measuring range are classified per NAMUR NE43: 3.8 to
+//! 20.5 mA is valid, the bands just outside it are saturated readings, and
+//! below 3.6 mA or above 21 mA signals a failure such as a broken wire.
+
+use crate::{AdcError, Measurement, Mux, QwiicADC, SampleRates, Scaling, PGA};
+
+/// Lowest valid loop current in milliamps
+const VALID_LOW_MA: f32 = 3.8;
+/// Highest valid loop current in milliamps
+const VALID_HIGH_MA: f32 = 20.5;
+/// Loop currents at or below this signal a failure
+const FAILURE_LOW_MA: f32 = 3.6;
+/// Loop currents at or above this signal a failure
+const FAILURE_HIGH_MA: f32 = 21.0;
+/// Largest loop current the gain setting must accommodate
+const HEADROOM_MA: f32 = 22.0;
+
+/// NAMUR NE43 classification of a loop current
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopStatus {
+    /// 3.8 to 20.5 mA
+    Valid,
+    /// 3.6 to 3.8 mA, the transmitter is saturated below its range
+    SaturatedLow,
+    /// 20.5 to 21 mA, the transmitter is saturated above its range
+    SaturatedHigh,
+    /// 3.6 mA or less: broken wire, lost power or a transmitter fault
+    FailureLow,
+    /// 21 mA or more: short circuit or a transmitter fault
+    FailureHigh,
+}
+
+impl LoopStatus {
+    /// Classify a loop current in milliamps
+    pub fn from_current(milliamps: f32) -> LoopStatus {
+        if milliamps <= FAILURE_LOW_MA {
+            LoopStatus::FailureLow
+        } else if milliamps < VALID_LOW_MA {
+            LoopStatus::SaturatedLow
+        } else if milliamps <= VALID_HIGH_MA {
+            LoopStatus::Valid
+        } else if milliamps < FAILURE_HIGH_MA {
+            LoopStatus::SaturatedHigh
+        } else {
+            LoopStatus::FailureHigh
+        }
+    }
+
+    /// Check if the status signals a failure rather than a measurement
+    pub fn is_failure(self) -> bool {
+        matches!(self, LoopStatus::FailureLow | LoopStatus::FailureHigh)
+    }
+}
+
+/// A current loop reading
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopReading {
+    /// Loop current in milliamps
+    pub current_ma: f32,
+    /// Current scaled onto the engineering range
+    pub measurement: Measurement,
+    /// NAMUR NE43 classification of the current
+    pub status: LoopStatus,
+}
+
+/// A 4–20 mA transmitter read through a shunt resistor
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurrentLoop {
+    /// Input across the shunt
+    pub input: Mux,
+    /// Shunt resistance in ohms
+    pub shunt_ohms: f32,
+    /// PGA gain setting (default: the narrowest range holding 22 mA across the shunt)
+    pub gain: PGA,
+    /// Data rate (default: 1600 SPS)
+    pub rate: SampleRates,
+    scaling: Scaling,
+}
+
+impl CurrentLoop {
+    /// Create a current loop
+    ///
+    /// # Arguments
+    /// * `input` - Input across the shunt
+    /// * `shunt_ohms` - Shunt resistance in ohms, e.g. 100
+    /// * `low` - Engineering value at 4 mA
+    /// * `high` - Engineering value at 20 mA
+    /// * `unit` - Engineering unit
+    pub fn new(input: Mux, shunt_ohms: f32, low: f32, high: f32, unit: &str) -> CurrentLoop {
+        let full_scale_mv = HEADROOM_MA * shunt_ohms;
+        let gain = PGA::RANGES.iter()
+            .rev()
+            .copied()
+            .find(|gain| gain.full_scale_mv() >= full_scale_mv)
+            .unwrap_or(PGA::TwoThirds);
+        CurrentLoop {
+            input,
+            shunt_ohms,
+            gain,
+            rate: SampleRates::S1600Hz,
+            scaling: Scaling::linear((high - low) / 16.0, low - 4.0 * (high - low) / 16.0, unit),
+        }
+    }
+
+    /// Set the PGA gain setting
+    pub fn with_gain(mut self, gain: PGA) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Set the data rate
+    pub fn with_sample_rate(mut self, rate: SampleRates) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Convert the voltage across the shunt into a reading
+    pub fn reading(&self, volts: f32) -> LoopReading {
+        let current_ma = volts / self.shunt_ohms * 1000.0;
+        LoopReading {
+            current_ma,
+            measurement: self.scaling.measure(current_ma),
+            status: LoopStatus::from_current(current_ma),
+        }
+    }
+}
+
+impl QwiicADC {
+    /// Read a 4–20 mA current loop
+    ///
+    /// Configured calibration is applied to the shunt voltage.
+    pub fn read_current_loop(&mut self, current_loop: &CurrentLoop) -> Result<LoopReading, AdcError> {
+        let volts = self.read_volts(current_loop.input, current_loop.gain, current_loop.rate)?;
+        Ok(current_loop.reading(volts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_bands() {
+        assert_eq!(LoopStatus::from_current(0.0), LoopStatus::FailureLow);
+        assert_eq!(LoopStatus::from_current(3.6), LoopStatus::FailureLow);
+        assert_eq!(LoopStatus::from_current(3.7), LoopStatus::SaturatedLow);
+        assert_eq!(LoopStatus::from_current(3.8), LoopStatus::Valid);
+        assert_eq!(LoopStatus::from_current(12.0), LoopStatus::Valid);
+        assert_eq!(LoopStatus::from_current(20.5), LoopStatus::Valid);
+        assert_eq!(LoopStatus::from_current(20.8), LoopStatus::SaturatedHigh);
+        assert_eq!(LoopStatus::from_current(21.0), LoopStatus::FailureHigh);
+
+        assert!(LoopStatus::FailureLow.is_failure());
+        assert!(!LoopStatus::SaturatedHigh.is_failure());
+    }
+
+    #[test]
+    fn test_scaling() {
+        // 0-10 bar transmitter on a 100 ohm shunt
+        let current_loop = CurrentLoop::new(Mux::Single0, 100.0, 0.0, 10.0, "bar");
+        let reading = current_loop.reading(0.4);
+        assert!((reading.current_ma - 4.0).abs() < 1e-4);
+        assert!(reading.measurement.value.abs() < 1e-4);
+        assert_eq!(reading.measurement.unit, "bar");
+
+        let reading = current_loop.reading(1.2);
+        assert!((reading.measurement.value - 5.0).abs() < 1e-4);
+        assert!((current_loop.reading(2.0).measurement.value - 10.0).abs() < 1e-4);
+
+        // Broken wire
+        let reading = current_loop.reading(0.0);
+        assert_eq!(reading.status, LoopStatus::FailureLow);
+        assert!((reading.measurement.value + 2.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_default_gain() {
+        // 22 mA across 100 ohms is 2.2 V
+        assert_eq!(CurrentLoop::new(Mux::Single0, 100.0, 0.0, 1.0, "").gain, PGA::One);
+        // 22 mA across 250 ohms is 5.5 V
+        assert_eq!(CurrentLoop::new(Mux::Single0, 250.0, 0.0, 1.0, "").gain, PGA::TwoThirds);
+        // 22 mA across 10 ohms is 220 mV
+        assert_eq!(CurrentLoop::new(Mux::DiffP0N1, 10.0, 0.0, 1.0, "").gain, PGA::Sixteen);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_current_loop_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+
+        let current_loop = CurrentLoop::new(Mux::Single0, 100.0, 0.0, 10.0, "bar");
+        let reading = adc.read_current_loop(&current_loop).expect("Failed to read");
+        assert!(!reading.status.is_failure(), "Loop at {:.2} mA is {:?}", reading.current_ma, reading.status);
+        assert_eq!(reading.status, LoopStatus::from_current(reading.current_ma));
+        assert_eq!(reading.measurement.unit, "bar");
+    }
+}
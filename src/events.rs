@@ -0,0 +1,394 @@
+//! Software threshold events
+//!
+//! The hardware comparator watches a single input. An [`EventEngine`] instead
+//! evaluates named [`Rule`]s against readings of any input from streams,
+//! acquisitions or scans, applying hysteresis and debounce counts, and emits an
+//! [`Event`] whenever a rule becomes active or clears. Events are returned,
+//! passed to registered callbacks and sent to subscribed channels.
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Instant;
+
+use crate::{Mux, Sample, ScanRecord};
+
+/// Condition evaluated by a rule, in volts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuleCondition {
+    /// Active above the level
+    Above(f32),
+    /// Active below the level
+    Below(f32),
+    /// Active between `low` and `high`
+    Inside {
+        /// Lower edge of the window
+        low: f32,
+        /// Upper edge of the window
+        high: f32,
+    },
+    /// Active below `low` or above `high`
+    Outside {
+        /// Lower edge of the window
+        low: f32,
+        /// Upper edge of the window
+        high: f32,
+    },
+    /// Active while the input changes faster than this many volts per second,
+    /// in either direction
+    RateOfChange(f32),
+}
+
+impl RuleCondition {
+    /// Evaluate the condition given its current state
+    ///
+    /// An active condition only clears once the value is `hysteresis` beyond
+    /// the level that activated it.
+    fn evaluate(self, value: f32, rate: Option<f32>, active: bool, hysteresis: f32) -> bool {
+        let h = if active { hysteresis } else { 0.0 };
+        match self {
+            RuleCondition::Above(level) => value > level - h,
+            RuleCondition::Below(level) => value < level + h,
+            RuleCondition::Inside { low, high } => value >= low - h && value <= high + h,
+            RuleCondition::Outside { low, high } => value < low + h || value > high - h,
+            RuleCondition::RateOfChange(limit) => match rate {
+                Some(rate) => rate.abs() > limit - h,
+                None => active,
+            },
+        }
+    }
+}
+
+/// A named condition on one input
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    /// Name reported in events
+    pub name: String,
+    /// Input the rule applies to
+    pub input: Mux,
+    /// Condition that makes the rule active
+    pub condition: RuleCondition,
+    /// Distance beyond the activating level needed to clear, in volts (or volts
+    /// per second for rate-of-change rules) (default: 0)
+    pub hysteresis: f32,
+    /// Number of consecutive readings needed to change state (default: 1)
+    pub debounce: u32,
+}
+
+impl Rule {
+    /// Create a rule without hysteresis or debouncing
+    pub fn new(name: &str, input: Mux, condition: RuleCondition) -> Rule {
+        Rule {
+            name: name.to_string(),
+            input,
+            condition,
+            hysteresis: 0.0,
+            debounce: 1,
+        }
+    }
+
+    /// Set the hysteresis
+    pub fn with_hysteresis(mut self, hysteresis: f32) -> Self {
+        self.hysteresis = hysteresis.abs();
+        self
+    }
+
+    /// Set the number of consecutive readings needed to change state
+    pub fn with_debounce(mut self, readings: u32) -> Self {
+        self.debounce = readings.max(1);
+        self
+    }
+}
+
+/// Whether a rule became active or cleared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// The rule's condition became active
+    Triggered,
+    /// The rule's condition is no longer active
+    Cleared,
+}
+
+/// A change of state of a rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// Name of the rule
+    pub rule: String,
+    /// Input the rule applies to
+    pub input: Mux,
+    /// New state of the rule
+    pub kind: EventKind,
+    /// Reading that caused the change, in volts
+    pub value: f32,
+    /// Time of the reading
+    pub timestamp: Instant,
+}
+
+/// A rule with its evaluation state
+struct RuleState {
+    rule: Rule,
+    active: bool,
+    pending: u32,
+    previous: Option<(f32, Instant)>,
+}
+
+impl RuleState {
+    /// Evaluate a reading, returning the new state if it changed
+    fn update(&mut self, value: f32, timestamp: Instant) -> Option<EventKind> {
+        let rate = self.previous.and_then(|(previous, then)| {
+            let dt = timestamp.saturating_duration_since(then).as_secs_f32();
+            (dt > 0.0).then(|| (value - previous) / dt)
+        });
+        self.previous = Some((value, timestamp));
+
+        let condition = self.rule.condition.evaluate(value, rate, self.active, self.rule.hysteresis);
+        if condition == self.active {
+            self.pending = 0;
+            return None;
+        }
+
+        self.pending += 1;
+        if self.pending < self.rule.debounce {
+            return None;
+        }
+        self.pending = 0;
+        self.active = condition;
+        Some(if condition { EventKind::Triggered } else { EventKind::Cleared })
+    }
+}
+
+/// Handler called for every event
+type Callback = Box<dyn FnMut(&Event) + Send>;
+
+/// Evaluates rules against readings and dispatches events
+#[derive(Default)]
+pub struct EventEngine {
+    rules: Vec<RuleState>,
+    callbacks: Vec<Callback>,
+    subscribers: Vec<Sender<Event>>,
+}
+
+impl EventEngine {
+    /// Create an engine without rules
+    pub fn new() -> EventEngine {
+        EventEngine::default()
+    }
+
+    /// Add a rule, initially inactive
+    pub fn add_rule(&mut self, rule: Rule) {
+        self.rules.push(RuleState {
+            rule,
+            active: false,
+            pending: 0,
+            previous: None,
+        });
+    }
+
+    /// Add a rule, initially inactive
+    pub fn with_rule(mut self, rule: Rule) -> Self {
+        self.add_rule(rule);
+        self
+    }
+
+    /// Remove all rules, keeping callbacks and subscribers
+    pub fn clear_rules(&mut self) {
+        self.rules.clear();
+    }
+
+    /// Call `callback` for every event
+    pub fn on_event<F>(&mut self, callback: F)
+    where
+        F: FnMut(&Event) + Send + 'static,
+    {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Get a channel receiving every event
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Check if a rule is currently active
+    pub fn is_active(&self, name: &str) -> bool {
+        self.rules.iter().any(|state| state.rule.name == name && state.active)
+    }
+
+    /// Evaluate a reading against every rule for its input
+    ///
+    /// # Arguments
+    /// * `input` - Input the reading was taken from
+    /// * `value` - Reading in volts
+    /// * `timestamp` - Time of the reading, used for rate-of-change rules
+    ///
+    /// # Returns
+    /// The events caused by the reading, which have also been dispatched
+    pub fn evaluate(&mut self, input: Mux, value: f32, timestamp: Instant) -> Vec<Event> {
+        let mut events = Vec::new();
+        for state in self.rules.iter_mut().filter(|state| state.rule.input == input) {
+            if let Some(kind) = state.update(value, timestamp) {
+                events.push(Event {
+                    rule: state.rule.name.clone(),
+                    input,
+                    kind,
+                    value,
+                    timestamp,
+                });
+            }
+        }
+
+        for event in &events {
+            for callback in &mut self.callbacks {
+                callback(event);
+            }
+            self.subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+        }
+        events
+    }
+
+    /// Evaluate a sample from a stream or acquisition
+    pub fn process_sample(&mut self, sample: &Sample) -> Vec<Event> {
+        self.evaluate(sample.input, sample.volts, sample.timestamp)
+    }
+
+    /// Evaluate every value of a scan record
+    pub fn process_scan(&mut self, record: &ScanRecord) -> Vec<Event> {
+        record.values.iter()
+            .flat_map(|value| self.evaluate(value.input, value.volts, value.timestamp))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Feed values 10 ms apart, returning the kinds of the events produced
+    fn feed(engine: &mut EventEngine, input: Mux, values: &[f32]) -> Vec<(usize, EventKind)> {
+        let start = Instant::now();
+        values.iter()
+            .enumerate()
+            .flat_map(|(i, &value)| {
+                engine.evaluate(input, value, start + Duration::from_millis(10 * i as u64))
+                    .into_iter()
+                    .map(move |event| (i, event.kind))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_above_with_hysteresis() {
+        let mut engine = EventEngine::new()
+            .with_rule(Rule::new("high", Mux::Single0, RuleCondition::Above(2.0)).with_hysteresis(0.2));
+        let events = feed(&mut engine, Mux::Single0, &[1.0, 2.1, 1.9, 2.1, 1.7, 1.9]);
+        assert_eq!(events, vec![(1, EventKind::Triggered), (4, EventKind::Cleared)]);
+    }
+
+    #[test]
+    fn test_below_and_other_inputs() {
+        let mut engine = EventEngine::new()
+            .with_rule(Rule::new("low", Mux::Single1, RuleCondition::Below(0.5)));
+        assert!(feed(&mut engine, Mux::Single0, &[0.0, 0.1]).is_empty(), "Other inputs are ignored");
+        assert_eq!(feed(&mut engine, Mux::Single1, &[0.4]), vec![(0, EventKind::Triggered)]);
+        assert!(engine.is_active("low"));
+    }
+
+    #[test]
+    fn test_window_rules() {
+        let mut engine = EventEngine::new()
+            .with_rule(Rule::new("in", Mux::DiffP0N1, RuleCondition::Inside { low: -1.0, high: 1.0 }))
+            .with_rule(Rule::new("out", Mux::DiffP0N1, RuleCondition::Outside { low: -1.0, high: 1.0 }));
+        feed(&mut engine, Mux::DiffP0N1, &[0.0]);
+        assert!(engine.is_active("in") && !engine.is_active("out"));
+        feed(&mut engine, Mux::DiffP0N1, &[-1.5]);
+        assert!(!engine.is_active("in") && engine.is_active("out"));
+    }
+
+    #[test]
+    fn test_debounce() {
+        let mut engine = EventEngine::new()
+            .with_rule(Rule::new("high", Mux::Single0, RuleCondition::Above(1.0)).with_debounce(3));
+        let events = feed(&mut engine, Mux::Single0, &[2.0, 2.0, 0.0, 2.0, 2.0, 2.0]);
+        assert_eq!(events, vec![(5, EventKind::Triggered)], "A glitch should reset the count");
+    }
+
+    #[test]
+    fn test_rate_of_change() {
+        // 10 ms between readings: a 0.1 V step is 10 V/s
+        let mut engine = EventEngine::new()
+            .with_rule(Rule::new("slew", Mux::Single2, RuleCondition::RateOfChange(5.0)));
+        let events = feed(&mut engine, Mux::Single2, &[1.0, 1.01, 1.11, 1.0, 1.0]);
+        assert_eq!(events, vec![(2, EventKind::Triggered), (4, EventKind::Cleared)]);
+    }
+
+    #[test]
+    fn test_callbacks_and_subscribers() {
+        let mut engine = EventEngine::new()
+            .with_rule(Rule::new("high", Mux::Single0, RuleCondition::Above(1.0)));
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        engine.on_event(move |event| sink.lock().unwrap().push(event.rule.clone()));
+        let receiver = engine.subscribe();
+
+        feed(&mut engine, Mux::Single0, &[0.0, 2.0, 0.0]);
+        assert_eq!(*seen.lock().unwrap(), vec!["high", "high"]);
+
+        let received: Vec<EventKind> = receiver.try_iter().map(|event| event.kind).collect();
+        assert_eq!(received, vec![EventKind::Triggered, EventKind::Cleared]);
+
+        // Dropped receivers are removed rather than failing
+        drop(receiver);
+        feed(&mut engine, Mux::Single0, &[2.0]);
+        assert_eq!(seen.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_process_scan() {
+        let now = Instant::now();
+        let record = ScanRecord {
+            timestamp: now,
+            values: vec![
+                crate::ScanValue { input: Mux::Single0, gain: crate::PGA::Two, counts: 0.0, volts: 0.2, measurement: None, timestamp: now },
+                crate::ScanValue { input: Mux::Single1, gain: crate::PGA::Two, counts: 0.0, volts: 1.8, measurement: None, timestamp: now },
+            ],
+        };
+        let mut engine = EventEngine::new()
+            .with_rule(Rule::new("a", Mux::Single0, RuleCondition::Above(1.0)))
+            .with_rule(Rule::new("b", Mux::Single1, RuleCondition::Above(1.0)));
+
+        let events = engine.process_scan(&record);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].rule, "b");
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_events_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+
+        // Every reading is above -3 V, so "floor" crosses its threshold once debounced
+        let mut engine = EventEngine::new()
+            .with_rule(Rule::new("floor", Mux::Single0, RuleCondition::Above(-3.0)).with_debounce(4))
+            .with_rule(Rule::new("high", Mux::Single0, RuleCondition::Above(1.5)).with_hysteresis(0.1).with_debounce(4));
+        let events = engine.subscribe();
+        for sample in adc.stream(0, crate::SampleRates::S1600Hz).expect("Failed to start stream").take(1600) {
+            engine.process_sample(&sample.expect("Failed to read"));
+        }
+
+        let events: Vec<Event> = events.try_iter().collect();
+        let floor: Vec<&Event> = events.iter().filter(|event| event.rule == "floor").collect();
+        assert_eq!(floor.len(), 1, "The always-true rule fires exactly once");
+        assert_eq!(floor[0].kind, EventKind::Triggered);
+        assert!(engine.is_active("floor"));
+
+        // Events of a rule alternate, and the last one gives its state
+        let high: Vec<&Event> = events.iter().filter(|event| event.rule == "high").collect();
+        for (i, event) in high.iter().enumerate() {
+            let expected = if i % 2 == 0 { EventKind::Triggered } else { EventKind::Cleared };
+            assert_eq!(event.kind, expected);
+        }
+        assert_eq!(engine.is_active("high"), high.last().is_some_and(|event| event.kind == EventKind::Triggered));
+    }
+}
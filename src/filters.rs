@@ -0,0 +1,550 @@
+//! Digital filters for sample streams
+//!
+//! Every filter implements [`Filter`] and processes one value at a time, so
+//! filters can be chained with [`FilterChain`] and attached per input with
+//! [`ChannelFilters`] to continuous-mode streams, acquisitions or scan results.
+//! Filters that need the sampling frequency take it in hertz; use
+//! `QwiicADC::data_rate_hz` to get it for a data rate setting.
+
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+use crate::averaging::median;
+use crate::{AdcError, Mux, Sample, ScanRecord};
+
+/// A filter processing one value at a time
+pub trait Filter: Send {
+    /// Feed a value through the filter and return the filtered value
+    fn process(&mut self, input: f32) -> f32;
+
+    /// Clear the filter state
+    fn reset(&mut self);
+}
+
+/// Mean of the last N values
+#[derive(Debug, Clone)]
+pub struct MovingAverage {
+    window: usize,
+    values: VecDeque<f32>,
+    // f64 so the incrementally updated sum does not drift over long runs
+    sum: f64,
+}
+
+impl MovingAverage {
+    /// Create a moving average over `window` values
+    pub fn new(window: usize) -> MovingAverage {
+        let window = window.max(1);
+        MovingAverage {
+            window,
+            values: VecDeque::with_capacity(window),
+            sum: 0.0,
+        }
+    }
+}
+
+impl Filter for MovingAverage {
+    fn process(&mut self, input: f32) -> f32 {
+        if self.values.len() == self.window {
+            self.sum -= self.values.pop_front().unwrap_or(0.0) as f64;
+        }
+        self.values.push_back(input);
+        self.sum += input as f64;
+        (self.sum / self.values.len() as f64) as f32
+    }
+
+    fn reset(&mut self) {
+        self.values.clear();
+        self.sum = 0.0;
+    }
+}
+
+/// Exponential moving average, `y += alpha * (x - y)`
+#[derive(Debug, Clone)]
+pub struct ExponentialMovingAverage {
+    alpha: f32,
+    state: Option<f32>,
+}
+
+impl ExponentialMovingAverage {
+    /// Create an exponential moving average
+    ///
+    /// # Arguments
+    /// * `alpha` - Weight of each new value, between 0 and 1
+    pub fn new(alpha: f32) -> ExponentialMovingAverage {
+        ExponentialMovingAverage {
+            alpha: alpha.clamp(0.0, 1.0),
+            state: None,
+        }
+    }
+}
+
+impl Filter for ExponentialMovingAverage {
+    fn process(&mut self, input: f32) -> f32 {
+        let output = match self.state {
+            Some(state) => state + self.alpha * (input - state),
+            None => input,
+        };
+        self.state = Some(output);
+        output
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/// Median of the last N values, removing isolated spikes
+#[derive(Debug, Clone)]
+pub struct Median {
+    window: usize,
+    values: VecDeque<f32>,
+}
+
+impl Median {
+    /// Create a median filter over `window` values
+    pub fn new(window: usize) -> Median {
+        let window = window.max(1);
+        Median {
+            window,
+            values: VecDeque::with_capacity(window),
+        }
+    }
+}
+
+impl Filter for Median {
+    fn process(&mut self, input: f32) -> f32 {
+        if self.values.len() == self.window {
+            self.values.pop_front();
+        }
+        self.values.push_back(input);
+        median(self.values.iter().copied().collect())
+    }
+
+    fn reset(&mut self) {
+        self.values.clear();
+    }
+}
+
+/// First-order IIR low-pass filter (RC equivalent)
+#[derive(Debug, Clone)]
+pub struct FirstOrderLowPass {
+    average: ExponentialMovingAverage,
+}
+
+impl FirstOrderLowPass {
+    /// Create a low-pass filter
+    ///
+    /// # Arguments
+    /// * `cutoff_hz` - -3 dB frequency
+    /// * `sample_rate_hz` - Rate at which values are fed to the filter
+    pub fn new(cutoff_hz: f32, sample_rate_hz: f32) -> FirstOrderLowPass {
+        let rc = 1.0 / (2.0 * PI * cutoff_hz);
+        let dt = 1.0 / sample_rate_hz;
+        FirstOrderLowPass {
+            average: ExponentialMovingAverage::new(dt / (rc + dt)),
+        }
+    }
+}
+
+impl Filter for FirstOrderLowPass {
+    fn process(&mut self, input: f32) -> f32 {
+        self.average.process(input)
+    }
+
+    fn reset(&mut self) {
+        self.average.reset();
+    }
+}
+
+/// Mains frequency for notch filters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MainsFrequency {
+    /// 50 Hz mains (Europe, Asia, Africa, Oceania)
+    Hz50,
+    /// 60 Hz mains (Americas, parts of Asia)
+    Hz60,
+}
+
+impl MainsFrequency {
+    /// Frequency in hertz
+    pub fn hz(self) -> f32 {
+        match self {
+            MainsFrequency::Hz50 => 50.0,
+            MainsFrequency::Hz60 => 60.0,
+        }
+    }
+}
+
+/// Second-order IIR section (biquad)
+///
+/// Coefficients follow the RBJ audio EQ cookbook. The state is primed with the
+/// first value so a constant input passes without a start-up transient.
+#[derive(Debug, Clone)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+    primed: bool,
+}
+
+impl Biquad {
+    /// Create a second-order Butterworth low-pass filter
+    ///
+    /// # Arguments
+    /// * `cutoff_hz` - -3 dB frequency, below half the sample rate
+    /// * `sample_rate_hz` - Rate at which values are fed to the filter
+    pub fn low_pass(cutoff_hz: f32, sample_rate_hz: f32) -> Biquad {
+        let (cos, alpha) = Biquad::prewarp(cutoff_hz, sample_rate_hz, std::f32::consts::FRAC_1_SQRT_2);
+        Biquad::normalized(
+            [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Create a notch filter
+    ///
+    /// # Arguments
+    /// * `frequency_hz` - Frequency to reject, below half the sample rate
+    /// * `sample_rate_hz` - Rate at which values are fed to the filter
+    /// * `q` - Quality factor; higher values give a narrower notch
+    pub fn notch(frequency_hz: f32, sample_rate_hz: f32, q: f32) -> Biquad {
+        let (cos, alpha) = Biquad::prewarp(frequency_hz, sample_rate_hz, q);
+        Biquad::normalized(
+            [1.0, -2.0 * cos, 1.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Create a notch filter rejecting mains hum
+    ///
+    /// # Arguments
+    /// * `mains` - Mains frequency to reject
+    /// * `sample_rate_hz` - Rate at which values are fed to the filter
+    pub fn mains_notch(mains: MainsFrequency, sample_rate_hz: f32) -> Biquad {
+        Biquad::notch(mains.hz(), sample_rate_hz, 5.0)
+    }
+
+    /// Cosine of the normalized frequency and the bandwidth term `alpha`
+    fn prewarp(frequency_hz: f32, sample_rate_hz: f32, q: f32) -> (f32, f32) {
+        let w0 = 2.0 * PI * frequency_hz / sample_rate_hz;
+        (w0.cos(), w0.sin() / (2.0 * q))
+    }
+
+    /// Build a filter from coefficients, dividing through by `a[0]`
+    fn normalized(b: [f32; 3], a: [f32; 3]) -> Biquad {
+        Biquad {
+            b0: b[0] / a[0],
+            b1: b[1] / a[0],
+            b2: b[2] / a[0],
+            a1: a[1] / a[0],
+            a2: a[2] / a[0],
+            z1: 0.0,
+            z2: 0.0,
+            primed: false,
+        }
+    }
+}
+
+impl Filter for Biquad {
+    fn process(&mut self, input: f32) -> f32 {
+        if !self.primed {
+            // Steady state for a constant input, scaled by the DC gain
+            let gain = (self.b0 + self.b1 + self.b2) / (1.0 + self.a1 + self.a2);
+            let output = input * gain;
+            self.z2 = self.b2 * input - self.a2 * output;
+            self.z1 = self.b1 * input - self.a1 * output + self.z2;
+            self.primed = true;
+        }
+
+        // Transposed direct form II
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+
+    fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+        self.primed = false;
+    }
+}
+
+/// Filters applied one after another
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn Filter>>,
+}
+
+impl FilterChain {
+    /// Create an empty chain, which passes values through unchanged
+    pub fn new() -> FilterChain {
+        FilterChain::default()
+    }
+
+    /// Append a filter to the end of the chain
+    pub fn then<F: Filter + 'static>(mut self, filter: F) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Number of filters in the chain
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Check if the chain has no filters
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+}
+
+impl Filter for FilterChain {
+    fn process(&mut self, input: f32) -> f32 {
+        self.filters.iter_mut().fold(input, |value, filter| filter.process(value))
+    }
+
+    fn reset(&mut self) {
+        for filter in &mut self.filters {
+            filter.reset();
+        }
+    }
+}
+
+/// Filter chains attached to individual inputs
+///
+/// Values of inputs without a chain pass through unchanged.
+#[derive(Default)]
+pub struct ChannelFilters {
+    chains: Vec<(Mux, FilterChain)>,
+}
+
+impl ChannelFilters {
+    /// Create an empty set of filters
+    pub fn new() -> ChannelFilters {
+        ChannelFilters::default()
+    }
+
+    /// Attach a chain to an input, replacing any chain already attached to it
+    pub fn with_chain(mut self, input: Mux, chain: FilterChain) -> Self {
+        self.chains.retain(|(existing, _)| *existing != input);
+        self.chains.push((input, chain));
+        self
+    }
+
+    /// Filter a value read from an input
+    pub fn process(&mut self, input: Mux, value: f32) -> f32 {
+        match self.chains.iter_mut().find(|(existing, _)| *existing == input) {
+            Some((_, chain)) => chain.process(value),
+            None => value,
+        }
+    }
+
+    /// Replace the volts of a sample with the filtered value
+    pub fn apply_sample(&mut self, sample: &mut Sample) {
+        sample.volts = self.process(sample.input, sample.volts);
+    }
+
+    /// Replace the volts of every value of a scan record with the filtered value
+    ///
+    /// Measurements are not rescaled; apply the input's `Scaling` to the filtered volts.
+    pub fn apply_scan(&mut self, record: &mut ScanRecord) {
+        for value in &mut record.values {
+            value.volts = self.process(value.input, value.volts);
+        }
+    }
+
+    /// Filter the samples of a stream as they are read
+    pub fn attach<I>(self, samples: I) -> FilteredSamples<I>
+    where
+        I: Iterator<Item = Result<Sample, AdcError>>,
+    {
+        FilteredSamples { samples, filters: self }
+    }
+
+    /// Clear the state of every chain
+    pub fn reset(&mut self) {
+        for (_, chain) in &mut self.chains {
+            chain.reset();
+        }
+    }
+}
+
+/// Iterator of samples with per-input filters applied to their volts
+///
+/// Created by `ChannelFilters::attach`; the raw values are left unchanged.
+pub struct FilteredSamples<I> {
+    samples: I,
+    filters: ChannelFilters,
+}
+
+impl<I> FilteredSamples<I> {
+    /// Get the underlying iterator back
+    pub fn into_inner(self) -> I {
+        self.samples
+    }
+}
+
+impl<I> Iterator for FilteredSamples<I>
+where
+    I: Iterator<Item = Result<Sample, AdcError>>,
+{
+    type Item = Result<Sample, AdcError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut sample = match self.samples.next()? {
+            Ok(sample) => sample,
+            Err(e) => return Some(Err(e)),
+        };
+        self.filters.apply_sample(&mut sample);
+        Some(Ok(sample))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Sine wave samples with the given frequency, amplitude and offset
+    fn sine(frequency_hz: f32, sample_rate_hz: f32, amplitude: f32, offset: f32, n: usize) -> Vec<f32> {
+        (0..n)
+            .map(|i| offset + amplitude * (2.0 * PI * frequency_hz * i as f32 / sample_rate_hz).sin())
+            .collect()
+    }
+
+    /// Peak-to-peak amplitude of the last `n` values
+    fn peak_to_peak(values: &[f32], n: usize) -> f32 {
+        let tail = &values[values.len() - n..];
+        let max = tail.iter().copied().fold(f32::MIN, f32::max);
+        let min = tail.iter().copied().fold(f32::MAX, f32::min);
+        max - min
+    }
+
+    fn run<F: Filter>(filter: &mut F, input: &[f32]) -> Vec<f32> {
+        input.iter().map(|&x| filter.process(x)).collect()
+    }
+
+    #[test]
+    fn test_moving_average() {
+        let mut filter = MovingAverage::new(4);
+        let output = run(&mut filter, &[4.0, 8.0, 0.0, 4.0, 8.0, 8.0]);
+        assert_eq!(output, vec![4.0, 6.0, 4.0, 4.0, 5.0, 5.0]);
+
+        filter.reset();
+        assert_eq!(filter.process(1.0), 1.0);
+    }
+
+    #[test]
+    fn test_moving_average_long_run() {
+        // The running sum must not drift over millions of samples
+        let mut filter = MovingAverage::new(16);
+        for i in 0..2_000_000 {
+            filter.process(1000.0 + 0.37 * (i % 7) as f32);
+        }
+        let output = run(&mut filter, &[0.5; 16]);
+        assert!((output[15] - 0.5).abs() < 1e-6, "Drifted to {}", output[15]);
+    }
+
+    #[test]
+    fn test_exponential_moving_average() {
+        let mut filter = ExponentialMovingAverage::new(0.5);
+        let output = run(&mut filter, &[0.0, 1.0, 1.0, 1.0]);
+        assert_eq!(output, vec![0.0, 0.5, 0.75, 0.875]);
+    }
+
+    #[test]
+    fn test_median_removes_spike() {
+        let mut filter = Median::new(3);
+        let output = run(&mut filter, &[1.0, 1.0, 9.0, 1.0, 1.0]);
+        assert!(output.iter().all(|&v| v == 1.0), "Spike should be removed: {:?}", output);
+    }
+
+    #[test]
+    fn test_first_order_low_pass() {
+        let fs = 1600.0;
+        let mut filter = FirstOrderLowPass::new(10.0, fs);
+        let output = run(&mut filter, &sine(400.0, fs, 1.0, 0.5, 800));
+        assert!(peak_to_peak(&output, 100) < 0.1, "High frequency should be attenuated");
+
+        let mut filter = FirstOrderLowPass::new(10.0, fs);
+        let output = run(&mut filter, &sine(0.5, fs, 1.0, 0.0, 6400));
+        assert!(peak_to_peak(&output, 3200) > 1.9, "Low frequency should pass");
+    }
+
+    #[test]
+    fn test_second_order_low_pass() {
+        let fs = 1600.0;
+        let mut filter = Biquad::low_pass(20.0, fs);
+        let output = run(&mut filter, &sine(400.0, fs, 1.0, 1.0, 800));
+        assert!(peak_to_peak(&output, 100) < 0.01, "High frequency should be attenuated");
+        assert!((output[799] - 1.0).abs() < 0.01, "DC should pass unchanged");
+    }
+
+    #[test]
+    fn test_biquad_constant_input_has_no_transient() {
+        let mut filter = Biquad::low_pass(20.0, 1600.0);
+        let output = run(&mut filter, &[2.5; 50]);
+        assert!(output.iter().all(|&v| (v - 2.5).abs() < 1e-4), "{:?}", output);
+    }
+
+    #[test]
+    fn test_mains_notch() {
+        let fs = 920.0;
+        let hum = sine(50.0, fs, 0.2, 1.0, 2000);
+        let mut filter = Biquad::mains_notch(MainsFrequency::Hz50, fs);
+        let output = run(&mut filter, &hum);
+        assert!(peak_to_peak(&output, 200) < 0.01, "50 Hz should be rejected");
+        assert!((output[1999] - 1.0).abs() < 0.01, "DC should pass unchanged");
+
+        // A 60 Hz notch leaves most of the 50 Hz signal
+        let mut filter = Biquad::mains_notch(MainsFrequency::Hz60, fs);
+        let output = run(&mut filter, &hum);
+        assert!(peak_to_peak(&output, 200) > 0.2);
+    }
+
+    #[test]
+    fn test_filter_chain() {
+        let mut chain = FilterChain::new()
+            .then(Median::new(3))
+            .then(MovingAverage::new(2));
+        assert_eq!(chain.len(), 2);
+        let output = run(&mut chain, &[2.0, 2.0, 100.0, 2.0, 4.0]);
+        assert_eq!(output, vec![2.0, 2.0, 2.0, 2.0, 3.0]);
+
+        let mut empty = FilterChain::new();
+        assert_eq!(empty.process(3.0), 3.0);
+    }
+
+    #[test]
+    fn test_channel_filters() {
+        let mut filters = ChannelFilters::new()
+            .with_chain(Mux::Single0, FilterChain::new().then(MovingAverage::new(2)));
+
+        assert_eq!(filters.process(Mux::Single0, 1.0), 1.0);
+        assert_eq!(filters.process(Mux::Single0, 3.0), 2.0);
+        // Inputs without a chain pass through
+        assert_eq!(filters.process(Mux::Single1, 3.0), 3.0);
+    }
+
+    #[test]
+    fn test_filtered_samples() {
+        let now = Instant::now();
+        let samples: Vec<Result<Sample, AdcError>> = [0.0, 2.0, 4.0]
+            .iter()
+            .enumerate()
+            .map(|(seq, &volts)| Ok(Sample { input: Mux::Single2, value: 0, volts, timestamp: now, seq: seq as u64 }))
+            .collect();
+
+        let filters = ChannelFilters::new()
+            .with_chain(Mux::Single2, FilterChain::new().then(MovingAverage::new(2)));
+        let volts: Vec<f32> = filters.attach(samples.into_iter())
+            .map(|sample| sample.unwrap().volts)
+            .collect();
+        assert_eq!(volts, vec![0.0, 1.0, 3.0]);
+    }
+}
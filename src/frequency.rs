@@ -0,0 +1,303 @@
+//! Frequency measurement of periodic inputs
+//!
+//! A [`FrequencyEstimator`] detects level crossings with hysteresis (a Schmitt
+//! trigger) and interpolates the crossing instants between samples, reporting
+//! frequency, period jitter and duty cycle over the most recent periods. Time
+//! is derived from the sample sequence and the chip's data rate, so
+//! conversions missed by a stream do not distort the result.
+
+use std::collections::VecDeque;
+
+use crate::{AdcError, Mux, QwiicADC, Sample, SampleRates, PGA};
+
+/// Default number of periods summarised by a report
+const DEFAULT_WINDOW: usize = 256;
+
+/// Result of a frequency measurement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrequencyReport {
+    /// Mean frequency in hertz
+    pub frequency: f32,
+    /// Mean period in seconds
+    pub period: f32,
+    /// Standard deviation of the individual periods in seconds
+    pub period_jitter: f32,
+    /// Fraction of each period spent above the level, between 0 and 1
+    pub duty_cycle: f32,
+    /// Number of complete periods measured
+    pub cycles: usize,
+}
+
+/// Level-crossing frequency estimator with hysteresis
+#[derive(Debug, Clone)]
+pub struct FrequencyEstimator {
+    sample_rate_hz: f32,
+    level: f32,
+    hysteresis: f32,
+    high: Option<bool>,
+    previous: Option<(f64, f32)>,
+    last_crossing: Option<f64>,
+    next_index: u64,
+    window: usize,
+    rising: VecDeque<f64>,
+    falling: VecDeque<f64>,
+}
+
+impl FrequencyEstimator {
+    /// Create an estimator
+    ///
+    /// # Arguments
+    /// * `sample_rate_hz` - Rate at which values are fed to the estimator
+    /// * `level` - Crossing level in volts
+    /// * `hysteresis` - Band around the level in volts; the input must leave it
+    ///   on the other side before the next crossing is counted
+    pub fn new(sample_rate_hz: f32, level: f32, hysteresis: f32) -> FrequencyEstimator {
+        FrequencyEstimator {
+            sample_rate_hz,
+            level,
+            hysteresis: hysteresis.abs(),
+            high: None,
+            previous: None,
+            last_crossing: None,
+            next_index: 0,
+            window: DEFAULT_WINDOW,
+            rising: VecDeque::new(),
+            falling: VecDeque::new(),
+        }
+    }
+
+    /// Set the number of most recent periods summarised by a report (default: 256)
+    pub fn with_window(mut self, periods: usize) -> Self {
+        self.window = periods.max(1);
+        self
+    }
+
+    /// Add the next value, taken one sample period after the previous one
+    pub fn push(&mut self, volts: f32) {
+        self.push_at(self.next_index, volts);
+    }
+
+    /// Add a sample, using its sequence number as the time base
+    pub fn push_sample(&mut self, sample: &Sample) {
+        self.push_at(sample.seq, sample.volts);
+    }
+
+    /// Add a value taken at sample index `index`
+    pub fn push_at(&mut self, index: u64, volts: f32) {
+        let time = index as f64;
+        let upper = self.level + self.hysteresis / 2.0;
+        let lower = self.level - self.hysteresis / 2.0;
+
+        // Remember where the input last passed the level itself; the hysteresis
+        // band is left some samples later, when the crossing is confirmed
+        if let Some((previous_time, previous_volts)) = self.previous {
+            if (previous_volts <= self.level) != (volts <= self.level) {
+                let fraction = (self.level - previous_volts) / (volts - previous_volts);
+                self.last_crossing = Some(previous_time + (time - previous_time) * fraction as f64);
+            }
+        }
+        self.previous = Some((time, volts));
+        self.next_index = index + 1;
+
+        let high = match self.high {
+            Some(false) if volts > upper => true,
+            Some(true) if volts < lower => false,
+            Some(state) => state,
+            None if volts > upper => true,
+            None if volts < lower => false,
+            None => return,
+        };
+
+        if self.high == Some(!high) {
+            let crossing = self.last_crossing.unwrap_or(time);
+            let crossings = if high { &mut self.rising } else { &mut self.falling };
+            if crossings.len() > self.window {
+                crossings.pop_front();
+            }
+            crossings.push_back(crossing);
+        }
+        self.high = Some(high);
+    }
+
+    /// Summarise the most recent periods
+    ///
+    /// # Returns
+    /// The measurement, or `None` until two rising crossings have been seen
+    pub fn report(&self) -> Option<FrequencyReport> {
+        if self.rising.len() < 2 {
+            return None;
+        }
+
+        let sample_period = 1.0 / self.sample_rate_hz as f64;
+        let rising: Vec<f64> = self.rising.iter().copied().collect();
+        let periods: Vec<f64> = rising.windows(2)
+            .map(|pair| (pair[1] - pair[0]) * sample_period)
+            .collect();
+        let cycles = periods.len();
+        let mean = periods.iter().sum::<f64>() / cycles as f64;
+        let variance = periods.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / cycles as f64;
+
+        // High time of each period: from its rising crossing to the next falling one
+        let mut high_fractions = Vec::with_capacity(cycles);
+        for pair in rising.windows(2) {
+            if let Some(&fall) = self.falling.iter().find(|&&fall| fall > pair[0] && fall < pair[1]) {
+                high_fractions.push((fall - pair[0]) / (pair[1] - pair[0]));
+            }
+        }
+        let duty_cycle = if high_fractions.is_empty() {
+            0.0
+        } else {
+            high_fractions.iter().sum::<f64>() / high_fractions.len() as f64
+        };
+
+        Some(FrequencyReport {
+            frequency: (1.0 / mean) as f32,
+            period: mean as f32,
+            period_jitter: variance.sqrt() as f32,
+            duty_cycle: duty_cycle as f32,
+            cycles,
+        })
+    }
+
+    /// Discard all crossings and start over
+    pub fn reset(&mut self) {
+        self.high = None;
+        self.previous = None;
+        self.last_crossing = None;
+        self.next_index = 0;
+        self.rising.clear();
+        self.falling.clear();
+    }
+}
+
+impl QwiicADC {
+    /// Measure the frequency of a periodic input from a continuous-mode stream
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential input
+    /// * `gain` - PGA gain setting
+    /// * `rate` - Data rate; must be well above twice the input frequency
+    /// * `level` - Crossing level in volts
+    /// * `hysteresis` - Band around the level in volts
+    /// * `samples` - Number of samples to take
+    ///
+    /// # Returns
+    /// The measurement, or `None` if fewer than two rising crossings were seen
+    pub fn measure_frequency(&mut self, input: Mux, gain: PGA, rate: SampleRates, level: f32, hysteresis: f32, samples: usize) -> Result<Option<FrequencyReport>, AdcError> {
+        let mut estimator = FrequencyEstimator::new(self.data_rate_hz(rate), level, hysteresis);
+        for sample in self.stream_input(input, gain, rate)?.take(samples) {
+            estimator.push_sample(&sample?);
+        }
+        Ok(estimator.report())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_sine_frequency() {
+        let fs = 490.0;
+        let mut estimator = FrequencyEstimator::new(fs, 1.0, 0.1);
+        for i in 0..2000 {
+            estimator.push(1.0 + 0.5 * (2.0 * PI * 12.3 * i as f32 / fs).sin());
+        }
+
+        let report = estimator.report().expect("Should see several cycles");
+        assert!((report.frequency - 12.3).abs() < 0.01, "Frequency was {}", report.frequency);
+        assert!((report.duty_cycle - 0.5).abs() < 0.01, "Duty cycle was {}", report.duty_cycle);
+        assert!(report.period_jitter < 1e-4);
+        assert!(report.cycles >= 48);
+    }
+
+    #[test]
+    fn test_square_wave_duty_cycle() {
+        // 25% duty cycle, 40 samples per period
+        let mut estimator = FrequencyEstimator::new(1600.0, 1.5, 0.2);
+        for i in 0..400 {
+            estimator.push(if i % 40 < 10 { 3.0 } else { 0.0 });
+        }
+
+        let report = estimator.report().unwrap();
+        assert!((report.frequency - 40.0).abs() < 0.01);
+        assert!((report.duty_cycle - 0.25).abs() < 0.01, "Duty cycle was {}", report.duty_cycle);
+    }
+
+    #[test]
+    fn test_hysteresis_rejects_noise() {
+        // Noise around the level must not add crossings
+        let mut estimator = FrequencyEstimator::new(1000.0, 0.0, 0.5);
+        for i in 0..1000 {
+            let square = if (i / 50) % 2 == 0 { 1.0 } else { -1.0 };
+            let chatter = if i % 50 < 3 { 0.2 * (i % 2) as f32 - 0.1 } else { 0.0 };
+            estimator.push(square * (if i % 50 < 3 { 0.1 } else { 1.0 }) + chatter);
+        }
+
+        let report = estimator.report().unwrap();
+        assert!((report.frequency - 10.0).abs() < 0.1, "Frequency was {}", report.frequency);
+    }
+
+    #[test]
+    fn test_missed_samples_use_sequence() {
+        let fs = 250.0;
+        let mut estimator = FrequencyEstimator::new(fs, 0.0, 0.05);
+        for seq in (0..1000u64).filter(|seq| seq % 7 != 3) {
+            let volts = (2.0 * PI * 5.0 * seq as f32 / fs).sin();
+            estimator.push_at(seq, volts);
+        }
+
+        let report = estimator.report().unwrap();
+        assert!((report.frequency - 5.0).abs() < 0.05, "Frequency was {}", report.frequency);
+    }
+
+    #[test]
+    fn test_window_limits_history() {
+        let mut estimator = FrequencyEstimator::new(1000.0, 0.5, 0.2).with_window(4);
+        // 100 periods of 20 samples, then 10 periods of 10 samples
+        for i in 0..2000 {
+            estimator.push(if i % 20 < 10 { 1.0 } else { 0.0 });
+        }
+        for i in 0..100 {
+            estimator.push(if i % 10 < 5 { 1.0 } else { 0.0 });
+        }
+
+        assert!(estimator.rising.len() <= 5 && estimator.falling.len() <= 5);
+        let report = estimator.report().unwrap();
+        assert_eq!(report.cycles, 4);
+        assert!((report.frequency - 100.0).abs() < 0.01, "Only recent periods count");
+    }
+
+    #[test]
+    fn test_report_needs_two_rising_crossings() {
+        let mut estimator = FrequencyEstimator::new(100.0, 0.0, 0.1);
+        for volts in [-1.0, 1.0, -1.0] {
+            estimator.push(volts);
+        }
+        assert_eq!(estimator.report(), None);
+
+        estimator.push(1.0);
+        assert!(estimator.report().is_some());
+
+        estimator.reset();
+        assert_eq!(estimator.report(), None);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_measure_frequency_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+
+        // A periodic signal crossing 1.65 V, e.g. 50 Hz, sampled for one second
+        let report = adc.measure_frequency(Mux::Single0, PGA::One, SampleRates::S1600Hz, 1.65, 0.1, 1600)
+            .expect("Failed to measure")
+            .expect("No periodic signal on the input");
+        let nyquist = adc.data_rate_hz(SampleRates::S1600Hz) / 2.0;
+        assert!(report.cycles >= 1);
+        assert!(report.frequency > 0.0 && report.frequency < nyquist, "{} Hz", report.frequency);
+        assert!((report.frequency * report.period - 1.0).abs() < 1e-3, "Period is the inverse of frequency");
+        assert!((0.0..=1.0).contains(&report.duty_cycle));
+        assert!(report.period_jitter >= 0.0 && report.period_jitter < report.period);
+    }
+}
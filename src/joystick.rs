@@ -0,0 +1,367 @@
+//! Joystick and potentiometer axes
+//!
+//! An [`Axis`] maps a wiper voltage onto a normalized position: -1 to 1 around
+//! a calibrated centre for sprung joystick axes, or 0 to 1 across the travel
+//! for knobs and sliders. The travel limits can be learned by moving the
+//! control through its range, and each axis has a dead zone, optional
+//! inversion and a response curve. A [`Joystick`] reads its two axes in one
+//! scan, so X and Y come from consecutive conversions.
+
+use std::time::Instant;
+
+use crate::{AdcError, Mux, QwiicADC, SampleRates, ScanEntry, ScanList, PGA};
+
+/// Range of the normalized output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AxisOutput {
+    /// -1 to 1, zero at the centre
+    Bipolar,
+    /// 0 to 1, zero at the minimum
+    Unipolar,
+}
+
+/// Response curve applied to the normalized position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResponseCurve {
+    /// Output follows the position
+    Linear,
+    /// Output is the position raised to a power, keeping its sign; powers
+    /// above 1 give finer control near zero
+    Power(f32),
+    /// Blend of linear and cubic response, from 0 (linear) to 1 (cubic)
+    Expo(f32),
+}
+
+impl ResponseCurve {
+    /// Apply the curve to a position between -1 and 1
+    pub fn apply(self, position: f32) -> f32 {
+        match self {
+            ResponseCurve::Linear => position,
+            ResponseCurve::Power(power) => position.signum() * position.abs().powf(power),
+            ResponseCurve::Expo(expo) => {
+                let expo = expo.clamp(0.0, 1.0);
+                expo * position.powi(3) + (1.0 - expo) * position
+            },
+        }
+    }
+}
+
+/// Calibration and mapping of one axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Axis {
+    /// Input of the wiper
+    pub input: Mux,
+    /// PGA gain setting (default: `PGA::One`)
+    pub gain: PGA,
+    /// Data rate (default: 1600 SPS)
+    pub rate: SampleRates,
+    /// Output range (default: bipolar)
+    pub output: AxisOutput,
+    /// Dead zone as a fraction of the travel (default: 0.05)
+    pub deadzone: f32,
+    /// Whether the output is reversed (default: false)
+    pub inverted: bool,
+    /// Response curve (default: linear)
+    pub curve: ResponseCurve,
+    /// Whether readings beyond the limits widen them (default: false)
+    pub learning: bool,
+    min: f32,
+    center: f32,
+    max: f32,
+}
+
+impl Axis {
+    /// Create an axis spanning 0 to 3.3 V with the centre at 1.65 V
+    pub fn new(input: Mux) -> Axis {
+        Axis {
+            input,
+            gain: PGA::One,
+            rate: SampleRates::S1600Hz,
+            output: AxisOutput::Bipolar,
+            deadzone: 0.05,
+            inverted: false,
+            curve: ResponseCurve::Linear,
+            learning: false,
+            min: 0.0,
+            center: 1.65,
+            max: 3.3,
+        }
+    }
+
+    /// Set the PGA gain setting
+    pub fn with_gain(mut self, gain: PGA) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Set the data rate
+    pub fn with_sample_rate(mut self, rate: SampleRates) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Set the output range
+    pub fn with_output(mut self, output: AxisOutput) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Set the dead zone as a fraction of the travel, from 0 to 0.99
+    pub fn with_deadzone(mut self, deadzone: f32) -> Self {
+        self.deadzone = deadzone.clamp(0.0, 0.99);
+        self
+    }
+
+    /// Reverse the output
+    pub fn with_inverted(mut self, inverted: bool) -> Self {
+        self.inverted = inverted;
+        self
+    }
+
+    /// Set the response curve
+    pub fn with_curve(mut self, curve: ResponseCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    /// Enable or disable learning of the travel limits
+    pub fn with_learning(mut self, learning: bool) -> Self {
+        self.learning = learning;
+        self
+    }
+
+    /// Set the travel limits in volts, with the centre halfway between them
+    pub fn with_range(mut self, min: f32, max: f32) -> Self {
+        self.min = min.min(max);
+        self.max = min.max(max);
+        self.center = (self.min + self.max) / 2.0;
+        self
+    }
+
+    /// Lowest wiper voltage of the travel
+    pub fn min(&self) -> f32 {
+        self.min
+    }
+
+    /// Wiper voltage at the centre
+    pub fn center(&self) -> f32 {
+        self.center
+    }
+
+    /// Highest wiper voltage of the travel
+    pub fn max(&self) -> f32 {
+        self.max
+    }
+
+    /// Set the centre voltage, widening the limits if it lies outside them
+    pub fn set_center(&mut self, volts: f32) {
+        self.center = volts;
+        self.min = self.min.min(volts);
+        self.max = self.max.max(volts);
+    }
+
+    /// Collapse the limits onto the centre so learning starts afresh
+    pub fn reset_range(&mut self) {
+        self.min = self.center;
+        self.max = self.center;
+    }
+
+    /// Widen the limits to include a wiper voltage
+    pub fn learn(&mut self, volts: f32) {
+        self.min = self.min.min(volts);
+        self.max = self.max.max(volts);
+    }
+
+    /// Map a wiper voltage onto the normalized output
+    ///
+    /// Learns the limits first when learning is enabled.
+    pub fn map(&mut self, volts: f32) -> f32 {
+        if self.learning {
+            self.learn(volts);
+        }
+
+        let deadzone = |position: f32| {
+            if position.abs() <= self.deadzone {
+                0.0
+            } else {
+                position.signum() * (position.abs() - self.deadzone) / (1.0 - self.deadzone)
+            }
+        };
+        let fraction = |offset: f32, span: f32| if span > 0.0 { offset / span } else { 0.0 };
+
+        match self.output {
+            AxisOutput::Bipolar => {
+                let offset = volts - self.center;
+                let span = if offset > 0.0 { self.max - self.center } else { self.center - self.min };
+                let position = self.curve.apply(deadzone(fraction(offset, span).clamp(-1.0, 1.0)));
+                if self.inverted { -position } else { position }
+            },
+            AxisOutput::Unipolar => {
+                let position = fraction(volts - self.min, self.max - self.min).clamp(0.0, 1.0);
+                let position = self.curve.apply(deadzone(position)).clamp(0.0, 1.0);
+                if self.inverted { 1.0 - position } else { position }
+            },
+        }
+    }
+}
+
+/// Position of a two-axis joystick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JoystickPosition {
+    /// Normalized X position
+    pub x: f32,
+    /// Normalized Y position
+    pub y: f32,
+    /// Time the axes were read
+    pub timestamp: Instant,
+}
+
+/// A two-axis joystick
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joystick {
+    /// X axis
+    pub x: Axis,
+    /// Y axis
+    pub y: Axis,
+}
+
+impl Joystick {
+    /// Create a joystick from its axes
+    pub fn new(x: Axis, y: Axis) -> Joystick {
+        Joystick { x, y }
+    }
+
+    /// Scan list reading the X and then the Y axis
+    pub fn scan_list(&self) -> ScanList {
+        ScanList::new()
+            .with_entry(ScanEntry::new(self.x.input).with_gain(self.x.gain).with_sample_rate(self.x.rate))
+            .with_entry(ScanEntry::new(self.y.input).with_gain(self.y.gain).with_sample_rate(self.y.rate))
+    }
+}
+
+impl QwiicADC {
+    /// Read a single axis, such as a knob
+    ///
+    /// Configured calibration is applied to the wiper voltage.
+    pub fn read_axis(&mut self, axis: &mut Axis) -> Result<f32, AdcError> {
+        let volts = self.read_volts(axis.input, axis.gain, axis.rate)?;
+        Ok(axis.map(volts))
+    }
+
+    /// Wiper voltages of both joystick axes from consecutive conversions
+    fn read_joystick_volts(&mut self, joystick: &Joystick) -> Result<(f32, f32, Instant), AdcError> {
+        let record = self.scan(&joystick.scan_list())?;
+        Ok((record.values[0].volts, record.values[1].volts, record.timestamp))
+    }
+
+    /// Read the position of a joystick
+    pub fn read_joystick(&mut self, joystick: &mut Joystick) -> Result<JoystickPosition, AdcError> {
+        let (x, y, timestamp) = self.read_joystick_volts(joystick)?;
+        Ok(JoystickPosition { x: joystick.x.map(x), y: joystick.y.map(y), timestamp })
+    }
+
+    /// Calibrate the centre of a released joystick
+    ///
+    /// # Arguments
+    /// * `joystick` - Joystick with the stick at rest
+    /// * `samples` - Scans averaged
+    pub fn calibrate_joystick_center(&mut self, joystick: &mut Joystick, samples: usize) -> Result<(), AdcError> {
+        let samples = samples.max(1);
+        let (mut x_total, mut y_total) = (0.0, 0.0);
+        for _ in 0..samples {
+            let (x, y, _) = self.read_joystick_volts(joystick)?;
+            x_total += x;
+            y_total += y;
+        }
+        joystick.x.set_center(x_total / samples as f32);
+        joystick.y.set_center(y_total / samples as f32);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bipolar_mapping() {
+        let mut axis = Axis::new(Mux::Single0).with_deadzone(0.0);
+        assert!(axis.map(1.65).abs() < 1e-6);
+        assert!((axis.map(3.3) - 1.0).abs() < 1e-6);
+        assert!((axis.map(0.0) + 1.0).abs() < 1e-6);
+        assert!((axis.map(2.475) - 0.5).abs() < 1e-5);
+        assert!((axis.map(5.0) - 1.0).abs() < 1e-6, "Clamped beyond the limit");
+
+        // An off-centre stick reaches full travel on both sides
+        axis.set_center(1.5);
+        assert!((axis.map(0.75) + 0.5).abs() < 1e-5);
+        assert!((axis.map(2.4) - 0.5).abs() < 1e-5);
+
+        axis.inverted = true;
+        assert!((axis.map(3.3) + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_deadzone() {
+        let mut axis = Axis::new(Mux::Single0).with_range(0.0, 2.0).with_deadzone(0.1);
+        assert_eq!(axis.map(1.05), 0.0);
+        assert_eq!(axis.map(0.95), 0.0);
+        // Output ramps from zero at the edge of the dead zone
+        assert!((axis.map(1.55) - 0.5).abs() < 1e-5);
+        assert!((axis.map(2.0) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_unipolar_and_learning() {
+        let mut knob = Axis::new(Mux::Single1)
+            .with_output(AxisOutput::Unipolar)
+            .with_deadzone(0.0)
+            .with_learning(true);
+        knob.reset_range();
+        for &volts in &[1.0, 0.2, 3.0] {
+            knob.map(volts);
+        }
+        assert_eq!((knob.min(), knob.max()), (0.2, 3.0));
+        assert!(knob.map(0.2).abs() < 1e-6);
+        assert!((knob.map(1.6) - 0.5).abs() < 1e-5);
+        assert!((knob.map(3.0) - 1.0).abs() < 1e-6);
+
+        knob.inverted = true;
+        assert!(knob.map(3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_response_curves() {
+        assert_eq!(ResponseCurve::Linear.apply(0.5), 0.5);
+        assert!((ResponseCurve::Power(2.0).apply(-0.5) + 0.25).abs() < 1e-6);
+        assert!((ResponseCurve::Expo(0.5).apply(0.5) - 0.3125).abs() < 1e-6);
+        assert_eq!(ResponseCurve::Expo(1.0).apply(1.0), 1.0);
+
+        let mut axis = Axis::new(Mux::Single0).with_deadzone(0.0).with_curve(ResponseCurve::Power(2.0));
+        assert!((axis.map(2.475) - 0.25).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_scan_list() {
+        let joystick = Joystick::new(Axis::new(Mux::Single0), Axis::new(Mux::Single1));
+        let list = joystick.scan_list();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.entries()[0].input, Mux::Single0);
+        assert_eq!(list.entries()[1].input, Mux::Single1);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_joystick_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+
+        // Stick released: after centering both axes read close to zero
+        let mut joystick = Joystick::new(Axis::new(Mux::Single0), Axis::new(Mux::Single1).with_inverted(true));
+        adc.calibrate_joystick_center(&mut joystick, 16).expect("Failed to calibrate");
+        assert!(joystick.x.min() <= joystick.x.center() && joystick.x.center() <= joystick.x.max());
+        for _ in 0..50 {
+            let position = adc.read_joystick(&mut joystick).expect("Failed to read");
+            assert!(position.x.abs() < 0.1 && position.y.abs() < 0.1, "Released stick at {:?}", position);
+        }
+    }
+}
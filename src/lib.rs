@@ -47,46 +47,66 @@ use std::thread;
 use std::time::Duration;
 use std::fmt;
 use std::error::Error;
+use std::io;
 
 use i2cdev::core::*;
 use i2cdev::linux::{LinuxI2CDevice, LinuxI2CError};
 
+pub mod acquisition;
+pub mod autorange;
+pub mod averaging;
+pub mod battery;
+pub mod calibration;
+pub mod capture;
+pub mod currentloop;
+pub mod events;
+pub mod filters;
+pub mod frequency;
+pub mod joystick;
+pub mod loadcell;
+pub mod noise;
+pub mod power;
+pub mod probe;
+pub mod ratiometric;
+pub mod reading;
+pub mod scaling;
+pub mod scan;
+pub mod spectrum;
+pub mod statistics;
+pub mod stream;
+pub mod thermistor;
+pub mod thermocouple;
+
+pub use acquisition::{Acquisition, AcquisitionConfig};
+pub use autorange::{AutoRange, AutoRangeReading};
+pub use averaging::AveragedReading;
+pub use battery::{BatteryLevel, BatteryMonitor, BatteryStatus, Chemistry};
+pub use calibration::{Calibration, CalibrationSet, CalibrationStep, Correction};
+pub use capture::{Capture, Trigger, TriggerCondition, TriggerMode};
+pub use currentloop::{CurrentLoop, LoopReading, LoopStatus};
+pub use events::{Event, EventEngine, EventKind, Rule, RuleCondition};
+pub use frequency::{FrequencyEstimator, FrequencyReport};
+pub use joystick::{Axis, AxisOutput, Joystick, JoystickPosition, ResponseCurve};
+pub use loadcell::{LoadCell, WeightReading, WeightUnit};
+pub use noise::{NoiseReport, NoiseResult};
+pub use power::{CurrentTransformer, PowerMeter, PowerReading, VoltageSensor};
+pub use probe::{BufferPoint, OrpProbe, PhCalibration, PhProbe, PhReading, ProbeTemperature, NERNST_SLOPE};
+pub use ratiometric::{Ratiometric, RatiometricReading};
+pub use reading::{ClipCounter, Reading, Saturation};
+pub use scaling::{Measurement, Scaling};
+pub use scan::{ScanEntry, ScanList, ScanRecord, ScanValue};
+pub use spectrum::{Spectrum, SpectrumBin, WindowFunction};
+pub use statistics::{StatsAccumulator, StatsWindow, WindowStats};
+pub use stream::{Sample, Stream};
+pub use thermistor::{DividerTopology, TemperatureUnit, Thermistor, ThermistorModel};
+pub use thermocouple::{ColdJunction, Thermocouple, ThermocoupleReading, ThermocoupleStatus, ThermocoupleType};
+
 /// Default delay in milliseconds for ADC conversion
 const DEFAULT_CONVERSION_DELAY_MS: u64 = 10;
 
 /// Default delay in microseconds for register operations
 const DEFAULT_REGISTER_DELAY_US: u64 = 10;
 
-/// ADC-specific error types
-#[derive(Debug)]
-pub enum AdcError {
-    /// Invalid channel number (must be 0-3 for single-ended)
-    InvalidChannel(u8),
-    /// Invalid differential mode configuration
-    InvalidDifferentialMode(u16),
-    /// I2C communication error
-    I2cError(LinuxI2CError),
-}
-
-impl fmt::Display for AdcError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            AdcError::InvalidChannel(ch) => write!(f, "Invalid channel: {}. Must be 0-3", ch),
-            AdcError::InvalidDifferentialMode(mode) => write!(f, "Invalid differential mode: 0x{:04X}", mode),
-            AdcError::I2cError(e) => write!(f, "I2C error: {}", e),
-        }
-    }
-}
-
-impl From<LinuxI2CError> for AdcError {
-    fn from(error: LinuxI2CError) -> Self {
-        AdcError::I2cError(error)
-    }
-}
-
-type ADCResult = Result<(), AdcError>;
-type ReadResult = Result<u16, AdcError>;
-
 /// I2C addresses for the ADS1015/ADS1115
 /// Address is determined by the ADDR pin connection
 #[derive(Copy, Clone)]
@@ -134,7 +154,7 @@ pub enum Modes {
 }
 
 /// Input multiplexer configuration
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Mux {
     /// Single-ended AIN0
     Single0 = 0x4000,
@@ -154,8 +174,37 @@ pub enum Mux {
     DiffP2N3 = 0x3000,
 }
 
+impl Mux {
+    /// Get the single-ended input for a channel number
+    ///
+    /// # Arguments
+    /// * `channel` - Channel number (must be 0-3)
+    ///
+    /// # Returns
+    /// * `Ok(mux)` - Single-ended input for the channel
+    /// * `Err(AdcError::InvalidChannel)` if channel > 3
+    pub fn single(channel: u8) -> Result<Mux, AdcError> {
+        match channel {
+            0 => Ok(Mux::Single0),
+            1 => Ok(Mux::Single1),
+            2 => Ok(Mux::Single2),
+            3 => Ok(Mux::Single3),
+            _ => Err(AdcError::InvalidChannel(channel)),
+        }
+    }
+
+    /// Get the input selected by the MUX bits of a config value
+    pub fn from_bits(config: u16) -> Option<Mux> {
+        const INPUTS: [Mux; 8] = [
+            Mux::DiffP0N1, Mux::DiffP0N3, Mux::DiffP1N3, Mux::DiffP2N3,
+            Mux::Single0, Mux::Single1, Mux::Single2, Mux::Single3,
+        ];
+        INPUTS.get(((config & 0x7000) >> 12) as usize).copied()
+    }
+}
+
 /// Data rate settings for ADS1015
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum SampleRates {
     /// 128 samples per second
     S128Hz = 0x0000,
@@ -170,12 +219,22 @@ pub enum SampleRates {
     /// 2400 samples per second
     S2400Hz = 0x00A0,
     /// 3300 samples per second
-    S3300Hz = 0x00C0
+    S3300Hz = 0x00C0,
+    /// 860 samples per second on the ADS1115; 3300 on the ADS1015, like `S3300Hz`
+    S860Hz = 0x00E0
+}
+
+impl SampleRates {
+    /// Data rate settings ordered from the slowest to the fastest
+    pub const RATES: [SampleRates; 8] = [
+        SampleRates::S128Hz, SampleRates::S250Hz, SampleRates::S490Hz, SampleRates::S920Hz,
+        SampleRates::S1600Hz, SampleRates::S2400Hz, SampleRates::S3300Hz, SampleRates::S860Hz,
+    ];
 }
 
 
 /// Programmable gain amplifier configuration
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum PGA {
     /// PGA mask
     Mask = 0x0E00,
@@ -193,6 +252,48 @@ pub enum PGA {
     Sixteen = 0x0A00
 }
 
+impl PGA {
+    /// Gain settings ordered from the widest to the narrowest range
+    pub const RANGES: [PGA; 6] = [PGA::TwoThirds, PGA::One, PGA::Two, PGA::Four, PGA::Eight, PGA::Sixteen];
+
+    /// Get the gain with the next wider input range, if any
+    pub fn wider(self) -> Option<PGA> {
+        let index = PGA::RANGES.iter().position(|&gain| gain == self)?;
+        index.checked_sub(1).map(|i| PGA::RANGES[i])
+    }
+
+    /// Get the gain with the next narrower input range, if any
+    pub fn narrower(self) -> Option<PGA> {
+        let index = PGA::RANGES.iter().position(|&gain| gain == self)?;
+        PGA::RANGES.get(index + 1).copied()
+    }
+
+    /// Get the gain selected by the PGA bits of a config value
+    pub fn from_bits(config: u16) -> PGA {
+        match config & (PGA::Mask as u16) {
+            0x0000 => PGA::TwoThirds,
+            0x0200 => PGA::One,
+            0x0400 => PGA::Two,
+            0x0600 => PGA::Four,
+            0x0800 => PGA::Eight,
+            _ => PGA::Sixteen,  // 0x0A00 and the two codes aliasing it
+        }
+    }
+
+    /// Full-scale range of the gain setting in millivolts
+    pub fn full_scale_mv(self) -> f32 {
+        match self {
+            PGA::TwoThirds => 6144.0,
+            PGA::One => 4096.0,
+            PGA::Two => 2048.0,
+            PGA::Four => 1024.0,
+            PGA::Eight => 512.0,
+            PGA::Sixteen => 256.0,
+            _ => 2048.0,  // Default
+        }
+    }
+}
+
 /// Comparator mode
 #[derive(Copy, Clone)]
 pub enum Cmode {
@@ -241,6 +342,16 @@ pub struct QwiicADCConfig {
     pub conversion_delay_ms: u64,
     /// Delay in microseconds for register operations (default: 10)
     pub register_delay_us: u64,
+    /// Number of conversions averaged by `get_single_ended` and `get_differential` (default: 1);
+    /// their mean is rounded to a whole code, `read_oversampled` keeps the fraction
+    pub oversampling: u16,
+    /// Reject samples further than this many scaled median absolute deviations
+    /// from the median when averaging (default: disabled)
+    pub outlier_rejection: Option<f32>,
+    /// Offset and gain corrections applied by `read_volts` and `scan` (default: none)
+    pub calibration: CalibrationSet,
+    /// Supply voltage of the chip, used to flag PGA ranges the inputs cannot reach (default: 3.3)
+    pub supply_volts: f32,
 }
 
 impl QwiicADCConfig {
@@ -250,6 +361,10 @@ impl QwiicADCConfig {
             model,
             conversion_delay_ms: DEFAULT_CONVERSION_DELAY_MS,
             register_delay_us: DEFAULT_REGISTER_DELAY_US,
+            oversampling: 1,
+            outlier_rejection: None,
+            calibration: CalibrationSet::new(),
+            supply_volts: 3.3,
         }
     }
     
@@ -264,6 +379,33 @@ impl QwiicADCConfig {
         self.register_delay_us = us;
         self
     }
+
+    /// Set the number of conversions averaged into each reading
+    pub fn with_oversampling(mut self, count: u16) -> Self {
+        self.oversampling = count.max(1);
+        self
+    }
+
+    /// Enable outlier rejection when averaging
+    ///
+    /// # Arguments
+    /// * `deviations` - Rejection threshold in scaled median absolute deviations (3.0 is typical)
+    pub fn with_outlier_rejection(mut self, deviations: f32) -> Self {
+        self.outlier_rejection = Some(deviations);
+        self
+    }
+
+    /// Set the calibration applied to readings
+    pub fn with_calibration(mut self, calibration: CalibrationSet) -> Self {
+        self.calibration = calibration;
+        self
+    }
+
+    /// Set the supply voltage of the chip
+    pub fn with_supply_voltage(mut self, volts: f32) -> Self {
+        self.supply_volts = volts;
+        self
+    }
 }
 
 impl Default for QwiicADCConfig {
@@ -280,6 +422,14 @@ pub enum AdcError {
     InvalidChannel(u8),
     /// I2C communication error
     I2cError(LinuxI2CError),
+    /// File access error when saving or loading data
+    Io(io::Error),
+    /// Calibration data that cannot be parsed or derived
+    InvalidCalibration(String),
+    /// The background acquisition thread panicked and the device was lost
+    AcquisitionPanicked,
+    /// Conversions were missed in a block that must be evenly spaced
+    MissedConversions(u64),
 }
 
 impl fmt::Display for AdcError {
@@ -289,6 +439,10 @@ impl fmt::Display for AdcError {
                 write!(f, "Invalid channel number: {}. Channel must be between 0 and 3", channel)
             }
             AdcError::I2cError(err) => write!(f, "I2C error: {}", err),
+            AdcError::Io(err) => write!(f, "I/O error: {}", err),
+            AdcError::InvalidCalibration(reason) => write!(f, "Invalid calibration data: {}", reason),
+            AdcError::AcquisitionPanicked => write!(f, "Acquisition thread panicked"),
+            AdcError::MissedConversions(count) => write!(f, "{} conversions missed while capturing a block", count),
         }
     }
 }
@@ -296,8 +450,10 @@ impl fmt::Display for AdcError {
 impl Error for AdcError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
-            AdcError::InvalidChannel(_) => None,
+            AdcError::InvalidChannel(_) | AdcError::InvalidCalibration(_) | AdcError::AcquisitionPanicked
+                | AdcError::MissedConversions(_) => None,
             AdcError::I2cError(err) => Some(err),
+            AdcError::Io(err) => Some(err),
         }
     }
 }
@@ -308,10 +464,17 @@ impl From<LinuxI2CError> for AdcError {
     }
 }
 
+impl From<io::Error> for AdcError {
+    fn from(err: io::Error) -> Self {
+        AdcError::Io(err)
+    }
+}
+
 /// Main struct for interacting with the Qwiic ADC
 pub struct QwiicADC {
     dev: LinuxI2CDevice,
     config: QwiicADCConfig,
+    clipping: ClipCounter,
 }
 
 type ADCResult = Result<(), AdcError>;
@@ -329,6 +492,7 @@ impl QwiicADC {
         Ok(QwiicADC {
             dev,
             config,
+            clipping: ClipCounter::new(),
         })
     }
     
@@ -363,6 +527,11 @@ impl QwiicADC {
         Ok(config & (PGA::Mask as u16))
     }
     
+    /// Get the current gain setting as a `PGA` value
+    pub fn active_gain(&mut self) -> Result<PGA, AdcError> {
+        Ok(PGA::from_bits(self.get_gain()?))
+    }
+
     /// Set the sample rate for the ADC
     ///
     /// # Arguments
@@ -429,17 +598,12 @@ impl QwiicADC {
     ///
     /// # Returns
     /// Voltage in millivolts
+    ///
+    /// Full-scale codes are converted like any other; use `read` to have
+    /// saturated conversions flagged.
     pub fn raw_to_voltage(&self, raw_value: u16, gain: PGA) -> f32 {
-        let fsrange = match gain {
-            PGA::TwoThirds => 6144.0,
-            PGA::One => 4096.0,
-            PGA::Two => 2048.0,
-            PGA::Four => 1024.0,
-            PGA::Eight => 512.0,
-            PGA::Sixteen => 256.0,
-            _ => 2048.0,  // Default
-        };
-        
+        let fsrange = gain.full_scale_mv();
+
         if self.config.model == "ADS1015" {
             // 12-bit ADC
             (raw_value as f32 / 2048.0) * fsrange
@@ -449,6 +613,83 @@ impl QwiicADC {
         }
     }
     
+    /// Interpret a raw conversion code as a signed two's complement value
+    ///
+    /// # Arguments
+    /// * `raw_value` - Raw ADC reading (12-bit for ADS1015, 16-bit for ADS1115)
+    pub fn raw_to_signed(&self, raw_value: u16) -> i16 {
+        if self.config.model == "ADS1015" {
+            ((raw_value << 4) as i16) >> 4
+        } else {
+            raw_value as i16
+        }
+    }
+
+    /// Convert a signed value back to the raw conversion format
+    ///
+    /// # Arguments
+    /// * `counts` - Signed ADC counts, see `raw_to_signed`
+    pub fn signed_to_raw(&self, counts: i16) -> u16 {
+        if self.config.model == "ADS1015" {
+            (counts as u16) & 0x0FFF
+        } else {
+            counts as u16
+        }
+    }
+
+    /// Convert a signed conversion result (or an average of several) to volts
+    ///
+    /// # Arguments
+    /// * `counts` - Signed ADC counts, see `raw_to_signed`
+    /// * `gain` - PGA gain setting used for the reading
+    pub fn counts_to_volts(&self, counts: f32, gain: PGA) -> f32 {
+        counts / self.full_scale_counts() * gain.full_scale_mv() / 1000.0
+    }
+
+    /// Number of counts corresponding to the positive full-scale voltage
+    pub(crate) fn full_scale_counts(&self) -> f32 {
+        if self.config.model == "ADS1015" {
+            2048.0
+        } else {
+            32768.0
+        }
+    }
+
+    /// Get the number of conversions per second for a data rate setting
+    ///
+    /// The ADS1115 uses the same register codes as the ADS1015 for slower rates.
+    ///
+    /// # Arguments
+    /// * `rate` - Sample rate setting
+    pub fn data_rate_hz(&self, rate: SampleRates) -> f32 {
+        self.data_rate_from_bits(rate as u16)
+    }
+
+    /// Get the number of conversions per second for the data rate bits of a config value
+    fn data_rate_from_bits(&self, config: u16) -> f32 {
+        const ADS1015_RATES: [f32; 8] = [128.0, 250.0, 490.0, 920.0, 1600.0, 2400.0, 3300.0, 3300.0];
+        const ADS1115_RATES: [f32; 8] = [8.0, 16.0, 32.0, 64.0, 128.0, 250.0, 475.0, 860.0];
+
+        let index = ((config & 0x00E0) >> 5) as usize;
+        if self.config.model == "ADS1015" {
+            ADS1015_RATES[index]
+        } else {
+            ADS1115_RATES[index]
+        }
+    }
+
+    /// Time to wait after changing the configuration in continuous mode until the
+    /// conversion register holds a result taken with the new configuration
+    ///
+    /// This covers the conversion in flight at the change plus one full conversion,
+    /// with 10% allowance for the tolerance of the internal oscillator.
+    ///
+    /// # Arguments
+    /// * `config` - Config value (or `SampleRates` value) holding the data rate bits
+    pub(crate) fn settling_time(&self, config: u16) -> Duration {
+        Duration::from_secs_f32(2.2 / self.data_rate_from_bits(config))
+    }
+
     /// Start a continuous conversion mode
     ///
     /// # Arguments
@@ -458,25 +699,108 @@ impl QwiicADC {
     /// * `Ok(())` if successful
     /// * `Err(AdcError::InvalidChannel)` if channel > 3
     pub fn start_continuous(&mut self, channel: u8) -> ADCResult {
-        if channel > 3 {
-            return Err(AdcError::InvalidChannel(channel));
-        }
-        
-        let mut config = (OS::Single as u16) | (Modes::Continuous as u16) | (SampleRates::S1600Hz as u16);
-        config |= PGA::Two as u16;
-        
-        config |= match channel {
-            0 => Mux::Single0 as u16,
-            1 => Mux::Single1 as u16,
-            2 => Mux::Single2 as u16,
-            3 => Mux::Single3 as u16,
-            _ => return Err(AdcError::InvalidChannel(channel)),
-        };
-        
+        let input = Mux::single(channel)?;
+        self.start_continuous_input(input, PGA::Two, SampleRates::S1600Hz)
+    }
+
+    /// Start continuous conversion mode on any input
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential input
+    /// * `gain` - PGA gain setting
+    /// * `rate` - Sample rate setting
+    pub fn start_continuous_input(&mut self, input: Mux, gain: PGA, rate: SampleRates) -> ADCResult {
+        let config = (OS::Single as u16) | (Modes::Continuous as u16) | (rate as u16)
+            | (gain as u16) | (input as u16);
+
         self.write_register(Pointers::Config as u8, config as usize)?;
         Ok(())
     }
+
+    /// Stream conversions from a single-ended channel in continuous mode
+    ///
+    /// Reads are paced to the data rate so each conversion is returned once;
+    /// continuous mode is stopped when the stream is dropped.
+    ///
+    /// # Arguments
+    /// * `channel` - Channel number (must be 0-3)
+    /// * `rate` - Sample rate setting
+    ///
+    /// # Returns
+    /// * `Ok(stream)` - Iterator of timestamped samples
+    /// * `Err(AdcError::InvalidChannel)` if channel > 3
+    pub fn stream(&mut self, channel: u8, rate: SampleRates) -> Result<Stream<'_>, AdcError> {
+        let input = Mux::single(channel)?;
+        self.stream_input(input, PGA::Two, rate)
+    }
+
+    /// Stream conversions from any input in continuous mode
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential input
+    /// * `gain` - PGA gain setting
+    /// * `rate` - Sample rate setting
+    pub fn stream_input(&mut self, input: Mux, gain: PGA, rate: SampleRates) -> Result<Stream<'_>, AdcError> {
+        Stream::start(self, input, gain, rate)
+    }
     
+    /// Switch the input while staying in continuous mode
+    ///
+    /// Only the MUX bits of the configuration are rewritten. The conversion in
+    /// flight when the input changes still belongs to the previous input, so this
+    /// waits for it to be replaced based on the configured data rate; the next
+    /// `read_last_conversion` returns a conversion of the new input.
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential input
+    pub fn switch_channel_continuous(&mut self, input: Mux) -> ADCResult {
+        let config = self.write_mux(input)?;
+        thread::sleep(self.settling_time(config));
+        Ok(())
+    }
+
+    /// Switch the input while staying in continuous mode, using the ALERT/RDY pin
+    ///
+    /// Like `switch_channel_continuous`, but instead of waiting based on the data
+    /// rate it calls `wait_ready` twice: once for the stale conversion and once for
+    /// the first conversion of the new input. `wait_ready` should block until the
+    /// ALERT/RDY pin signals a completed conversion (see `enable_conversion_ready_pin`).
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential input
+    /// * `wait_ready` - Blocks until the next conversion-ready pulse
+    pub fn switch_channel_continuous_ready<F>(&mut self, input: Mux, mut wait_ready: F) -> ADCResult
+    where
+        F: FnMut() -> ADCResult,
+    {
+        self.write_mux(input)?;
+        wait_ready()?;
+        wait_ready()
+    }
+
+    /// Configure the ALERT/RDY pin to pulse after every conversion
+    ///
+    /// This overwrites the comparator thresholds.
+    pub fn enable_conversion_ready_pin(&mut self) -> ADCResult {
+        self.set_high_threshold(0x8000)?;
+        self.set_low_threshold(0x0000)?;
+
+        let mut config = self.read_register_16bit(Pointers::Config as u8)?;
+        config &= !0x0003;  // Clear comparator queue bits
+        config |= Cque::OneConv as u16;
+        self.write_register(Pointers::Config as u8, config as usize)?;
+        Ok(())
+    }
+
+    /// Rewrite the MUX bits of the configuration, returning the new config value
+    fn write_mux(&mut self, input: Mux) -> Result<u16, AdcError> {
+        let mut config = self.read_register_16bit(Pointers::Config as u8)?;
+        config &= !0x7000;  // Clear MUX bits
+        config |= input as u16;  // Set new input
+        self.write_register(Pointers::Config as u8, config as usize)?;
+        Ok(config)
+    }
+
     /// Stop continuous conversion mode
     pub fn stop_continuous(&mut self) -> ADCResult {
         self.set_mode(Modes::Single)
@@ -499,25 +823,21 @@ impl QwiicADC {
     /// # Arguments
     /// * `channel` - Channel number (must be 0-3)
     ///
+    /// With oversampling configured the value is the mean of several conversions
+    /// rounded to a whole code; use `read_oversampled` for the fractional mean.
+    ///
     /// # Returns
     /// * `Ok(value)` - 12-bit ADC value for ADS1015, 16-bit for ADS1115
     /// * `Err(AdcError::InvalidChannel)` if channel > 3
     pub fn get_single_ended(&mut self, channel: u8) -> ReadResult {
-        if channel > 3 {
-            return Err(AdcError::InvalidChannel(channel));
+        let input = Mux::single(channel)?;
+        if self.config.oversampling > 1 {
+            return self.read_oversampled_raw(input);
         }
 
         let mut config = (OS::Single as u16) | (Modes::Single as u16) | (SampleRates::S1600Hz as u16);
         config |= PGA::Two as u16;
-
-        // Use match expression for clean channel selection
-        config |= match channel {
-            0 => Mux::Single0 as u16,
-            1 => Mux::Single1 as u16,
-            2 => Mux::Single2 as u16,
-            3 => Mux::Single3 as u16,
-            _ => return Err(AdcError::InvalidChannel(channel)),
-        };
+        config |= input as u16;
 
         self.write_register(Pointers::Config as u8, config as usize)?;
 
@@ -527,14 +847,13 @@ impl QwiicADC {
 
         let result = self.read_register_16bit(Pointers::Convert as u8)?;
         // For ADS1015, shift right by 4 bits (12-bit ADC)
-        if self.config.model == "ADS1015" {
-            Ok(result >> 4)
+        let raw = if self.config.model == "ADS1015" {
+            result >> 4
         } else {
-            Ok(result)
-        }
-
-      
-
+            result
+        };
+        self.count_clipping(input, raw);
+        Ok(raw)
     }
 
     /// Read a differential ADC value
@@ -556,6 +875,12 @@ impl QwiicADC {
             return Ok(0);
         }
 
+        if self.config.oversampling > 1 {
+            if let Some(input) = Mux::from_bits(config_mux_diff) {
+                return self.read_oversampled_raw(input);
+            }
+        }
+
         let mut config = (OS::Single as u16) | (Modes::Single as u16) | (SampleRates::S1600Hz as u16);
         config |= PGA::Two as u16;
         config |= config_mux_diff;
@@ -567,14 +892,40 @@ impl QwiicADC {
 
         let result = self.read_register_16bit(Pointers::Convert as u8)?;
         // For ADS1015, shift right by 4 bits (12-bit ADC)
-        if self.config.model == "ADS1015" {
-            Ok(result >> 4)
+        let raw = if self.config.model == "ADS1015" {
+            result >> 4
         } else {
-            Ok(result)
+            result
+        };
+        if let Some(input) = Mux::from_bits(config_mux_diff) {
+            self.count_clipping(input, raw);
         }
+        Ok(raw)
     }
 
 
+    /// Read any input in single-shot mode with the given settings
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential input
+    /// * `gain` - PGA gain setting
+    /// * `rate` - Sample rate setting
+    ///
+    /// # Returns
+    /// 12-bit ADC value for ADS1015, 16-bit for ADS1115
+    pub fn read_input(&mut self, input: Mux, gain: PGA, rate: SampleRates) -> ReadResult {
+        let config = (OS::Single as u16) | (Modes::Single as u16) | (rate as u16)
+            | (gain as u16) | (input as u16);
+
+        self.write_register(Pointers::Config as u8, config as usize)?;
+
+        // Wait for conversion to complete, allowing at least one conversion period
+        let period = Duration::from_secs_f32(1.1 / self.data_rate_hz(rate));
+        thread::sleep(period.max(Duration::from_millis(self.config.conversion_delay_ms)));
+
+        self.read_last_conversion()
+    }
+
     /// Convenience function to get analog data from a channel
     /// Wrapper around get_single_ended
     pub fn get_analog_data(&mut self, channel: u8) -> ReadResult {
@@ -616,6 +967,15 @@ impl QwiicADC {
     }
 }
 
+/// Open and initialize the device used by hardware tests
+#[cfg(test)]
+pub(crate) fn test_device(config: QwiicADCConfig) -> QwiicADC {
+    let mut adc = QwiicADC::new(config, "/dev/i2c-1", 0x48)
+        .expect("Could not init device");
+    adc.init().expect("Failed to initialize");
+    adc
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -673,6 +1033,43 @@ mod tests {
         assert_eq!(config.register_delay_us, 100);
     }
     
+    #[test]
+    fn test_averaging_configuration() {
+        let config = QwiicADCConfig::default();
+        assert_eq!(config.oversampling, 1);
+        assert_eq!(config.outlier_rejection, None);
+
+        let config = QwiicADCConfig::default()
+            .with_oversampling(16)
+            .with_outlier_rejection(3.0);
+        assert_eq!(config.oversampling, 16);
+        assert_eq!(config.outlier_rejection, Some(3.0));
+
+        let config = QwiicADCConfig::default().with_oversampling(0);
+        assert_eq!(config.oversampling, 1, "Oversampling should be at least 1");
+    }
+
+    #[test]
+    fn test_pga_from_bits() {
+        for gain in PGA::RANGES {
+            let config = (gain as u16) | (Mux::Single3 as u16) | (SampleRates::S3300Hz as u16);
+            assert_eq!(PGA::from_bits(config), gain);
+        }
+        assert_eq!(PGA::from_bits(0x0E00), PGA::Sixteen);
+    }
+
+    #[test]
+    fn test_mux_from_bits() {
+        let inputs = [
+            Mux::Single0, Mux::Single1, Mux::Single2, Mux::Single3,
+            Mux::DiffP0N1, Mux::DiffP0N3, Mux::DiffP1N3, Mux::DiffP2N3,
+        ];
+        for input in inputs {
+            let config = (input as u16) | (PGA::Four as u16) | (OS::Single as u16);
+            assert_eq!(Mux::from_bits(config), Some(input));
+        }
+    }
+
     #[test]
     #[ignore] // Requires hardware
     fn test_custom_timing_with_hardware() {
@@ -733,6 +1130,7 @@ mod tests {
         assert_eq!(SampleRates::S1600Hz as u16, 0x0080);
         assert_eq!(SampleRates::S2400Hz as u16, 0x00A0);
         assert_eq!(SampleRates::S3300Hz as u16, 0x00C0);
+        assert_eq!(SampleRates::S860Hz as u16, 0x00E0);
     }
 
     #[test]
@@ -792,6 +1190,7 @@ mod tests {
 
     #[test]
     #[ignore] // Requires hardware
+    #[allow(clippy::expect_fun_call)]
     fn test_get_single_ended_all_channels() {
         let config = QwiicADCConfig::default();
         let mut adc = QwiicADC::new(config, "/dev/i2c-1", 0x48)
@@ -837,6 +1236,7 @@ mod tests {
 
     #[test]
     #[ignore] // Requires hardware
+    #[allow(clippy::expect_fun_call)]
     fn test_get_differential_modes() {
         let config = QwiicADCConfig::default();
         let mut adc = QwiicADC::new(config, "/dev/i2c-1", 0x48)
@@ -943,6 +1343,7 @@ mod tests {
 
     #[test]
     #[ignore] // Requires hardware
+    #[allow(clippy::expect_fun_call)]
     fn test_gain_settings() {
         let config = QwiicADCConfig::default();
         let mut adc = QwiicADC::new(config, "/dev/i2c-1", 0x48)
@@ -969,6 +1370,7 @@ mod tests {
 
     #[test]
     #[ignore] // Requires hardware
+    #[allow(clippy::expect_fun_call)]
     fn test_sample_rate_settings() {
         let config = QwiicADCConfig::default();
         let mut adc = QwiicADC::new(config, "/dev/i2c-1", 0x48)
@@ -1103,6 +1505,46 @@ mod tests {
         }
     }
 
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_switch_channel_continuous() {
+        let config = QwiicADCConfig::default();
+        let mut adc = QwiicADC::new(config, "/dev/i2c-1", 0x48)
+            .expect("Could not init device");
+
+        adc.init().expect("Failed to initialize");
+
+        // Single-shot reference for each channel at the settings of start_continuous
+        let mut reference = [0i16; 4];
+        for channel in 0..4 {
+            let raw = adc.read_input(Mux::single(channel).unwrap(), PGA::Two, SampleRates::S1600Hz)
+                .expect("Failed to read reference");
+            reference[channel as usize] = adc.raw_to_signed(raw);
+        }
+        let tolerance = adc.full_scale_counts() as i32 / 20;
+
+        adc.start_continuous(0).expect("Failed to start continuous mode");
+        for channel in [1, 2, 3, 0] {
+            let input = Mux::single(channel).unwrap();
+            adc.switch_channel_continuous(input).expect("Failed to switch channel");
+            let mux = adc.read_register_16bit(Pointers::Config as u8)
+                .expect("Should read config register") & 0x7000;
+            assert_eq!(mux, input as u16, "Channel {} should be selected", channel);
+
+            let raw = adc.read_last_conversion().expect("Failed to read conversion");
+            let continuous = adc.raw_to_signed(raw);
+            let expected = reference[channel as usize];
+            assert!((continuous as i32 - expected as i32).abs() <= tolerance,
+                "Channel {} read {} after the switch, {} single-shot", channel, continuous, expected);
+        }
+
+        let mux = adc.read_register_16bit(Pointers::Config as u8)
+            .expect("Should read config register") & 0x7000;
+        assert_eq!(mux, Mux::Single0 as u16, "Only the last input should be selected");
+
+        adc.stop_continuous().expect("Failed to stop continuous mode");
+    }
+
     #[test]
     #[ignore] // Requires hardware
     fn test_mode_switching() {
@@ -0,0 +1,345 @@
+//! Strain-gauge load cells
+//!
+//! A [`LoadCell`] reads a bridge on a differential input at high gain, removes
+//! the tare offset and scales the result with a span factor found by weighing
+//! a known mass. Readings are averaged over a sliding window that restarts when
+//! the load changes, so the average follows slow creep without lagging behind
+//! a new load, and a reading is reported stable once the window is full and
+//! its spread is within the tolerance.
+
+use std::collections::VecDeque;
+
+use crate::{AdcError, Mux, QwiicADC, SampleRates, PGA};
+
+/// Unit of weight results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeightUnit {
+    /// Grams
+    Grams,
+    /// Kilograms
+    Kilograms,
+    /// Avoirdupois pounds
+    Pounds,
+    /// Avoirdupois ounces
+    Ounces,
+}
+
+impl WeightUnit {
+    /// Number of grams in one unit
+    pub fn grams(self) -> f32 {
+        match self {
+            WeightUnit::Grams => 1.0,
+            WeightUnit::Kilograms => 1000.0,
+            WeightUnit::Pounds => 453.592_37,
+            WeightUnit::Ounces => 28.349_523,
+        }
+    }
+
+    /// Unit symbol
+    pub fn symbol(self) -> &'static str {
+        match self {
+            WeightUnit::Grams => "g",
+            WeightUnit::Kilograms => "kg",
+            WeightUnit::Pounds => "lb",
+            WeightUnit::Ounces => "oz",
+        }
+    }
+}
+
+/// A weight reading
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeightReading {
+    /// Averaged weight in the load cell's unit
+    pub weight: f32,
+    /// Unit of the weight
+    pub unit: WeightUnit,
+    /// Whether the load has settled within the stability tolerance
+    pub stable: bool,
+    /// Bridge output of the latest reading in volts, before tare
+    pub volts: f32,
+}
+
+/// Load cell settings, tare and span calibration
+#[derive(Debug, Clone)]
+pub struct LoadCell {
+    /// Differential input of the bridge (default: `Mux::DiffP0N1`)
+    pub input: Mux,
+    /// PGA gain setting (default: `PGA::Sixteen`)
+    pub gain: PGA,
+    /// Data rate; slower rates have less noise (default: the slowest)
+    pub rate: SampleRates,
+    /// Conversions averaged into each reading (default: 4)
+    pub samples: usize,
+    /// Unit of results (default: grams)
+    pub unit: WeightUnit,
+    /// Largest spread of the window, in the load cell's unit, for a stable reading (default: 1)
+    pub tolerance: f32,
+    /// Number of readings in the averaging window (default: 8)
+    pub window: usize,
+    zero_volts: f32,
+    grams_per_volt: Option<f32>,
+    history: VecDeque<f32>,
+}
+
+impl Default for LoadCell {
+    fn default() -> Self {
+        LoadCell {
+            input: Mux::DiffP0N1,
+            gain: PGA::Sixteen,
+            rate: SampleRates::S128Hz,
+            samples: 4,
+            unit: WeightUnit::Grams,
+            tolerance: 1.0,
+            window: 8,
+            zero_volts: 0.0,
+            grams_per_volt: None,
+            history: VecDeque::new(),
+        }
+    }
+}
+
+impl LoadCell {
+    /// Create an uncalibrated load cell on `Mux::DiffP0N1`
+    pub fn new() -> LoadCell {
+        LoadCell::default()
+    }
+
+    /// Set the differential input of the bridge
+    pub fn with_input(mut self, input: Mux) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// Set the PGA gain setting
+    pub fn with_gain(mut self, gain: PGA) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Set the data rate
+    pub fn with_sample_rate(mut self, rate: SampleRates) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Set the number of conversions averaged into each reading
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    /// Set the unit of results, tolerance and calibration masses
+    pub fn with_unit(mut self, unit: WeightUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Set the stability criterion
+    ///
+    /// # Arguments
+    /// * `tolerance` - Largest spread of the window in the load cell's unit
+    /// * `window` - Number of readings averaged and checked for stability
+    pub fn with_stability(mut self, tolerance: f32, window: usize) -> Self {
+        self.tolerance = tolerance.abs();
+        self.window = window.max(1);
+        self
+    }
+
+    /// Set a previously measured calibration
+    ///
+    /// # Arguments
+    /// * `zero_volts` - Bridge output with no load
+    /// * `grams_per_volt` - Span factor
+    pub fn with_calibration(mut self, zero_volts: f32, grams_per_volt: f32) -> Self {
+        self.zero_volts = zero_volts;
+        self.grams_per_volt = Some(grams_per_volt);
+        self
+    }
+
+    /// Bridge output with no load, in volts
+    pub fn zero_volts(&self) -> f32 {
+        self.zero_volts
+    }
+
+    /// Span factor in grams per volt, once calibrated
+    pub fn grams_per_volt(&self) -> Option<f32> {
+        self.grams_per_volt
+    }
+
+    /// Set the no-load bridge output and restart averaging
+    pub fn set_zero(&mut self, volts: f32) {
+        self.zero_volts = volts;
+        self.history.clear();
+    }
+
+    /// Derive the span factor from the bridge output with a known mass
+    ///
+    /// # Arguments
+    /// * `volts` - Bridge output with the mass applied
+    /// * `mass` - Known mass in the load cell's unit
+    ///
+    /// # Returns
+    /// * `Ok(())` if the span was set
+    /// * `Err(AdcError::InvalidCalibration)` if the output equals the tare
+    pub fn set_span(&mut self, volts: f32, mass: f32) -> Result<(), AdcError> {
+        let span = volts - self.zero_volts;
+        if span == 0.0 || !span.is_finite() {
+            return Err(AdcError::InvalidCalibration("load cell output did not change with the span mass".to_string()));
+        }
+        self.grams_per_volt = Some(mass * self.unit.grams() / span);
+        self.history.clear();
+        Ok(())
+    }
+
+    /// Add a bridge output reading and return the averaged weight
+    ///
+    /// A reading further than the tolerance from the current average is taken
+    /// as a new load and restarts the window.
+    ///
+    /// # Returns
+    /// * `Ok(reading)` - The averaged weight
+    /// * `Err(AdcError::InvalidCalibration)` if the span has not been calibrated
+    pub fn push_volts(&mut self, volts: f32) -> Result<WeightReading, AdcError> {
+        let grams_per_volt = self.grams_per_volt
+            .ok_or_else(|| AdcError::InvalidCalibration("load cell span not calibrated".to_string()))?;
+        let weight = (volts - self.zero_volts) * grams_per_volt / self.unit.grams();
+
+        if let Some(mean) = self.mean() {
+            if (weight - mean).abs() > self.tolerance {
+                self.history.clear();
+            }
+        }
+        if self.history.len() == self.window {
+            self.history.pop_front();
+        }
+        self.history.push_back(weight);
+
+        let min = self.history.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = self.history.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        Ok(WeightReading {
+            weight: self.mean().unwrap_or(weight),
+            unit: self.unit,
+            stable: self.history.len() == self.window && max - min <= self.tolerance,
+            volts,
+        })
+    }
+
+    /// Mean of the averaging window
+    fn mean(&self) -> Option<f32> {
+        if self.history.is_empty() {
+            None
+        } else {
+            Some(self.history.iter().sum::<f32>() / self.history.len() as f32)
+        }
+    }
+}
+
+impl QwiicADC {
+    /// Read the bridge output of a load cell in volts, with the configured
+    /// calibration applied
+    fn read_bridge(&mut self, cell: &LoadCell, samples: usize) -> Result<f32, AdcError> {
+        let volts = self.read_averaged_with(cell.input, cell.gain, cell.rate, samples)?.volts;
+        Ok(self.config.calibration.apply(cell.input, cell.gain, volts))
+    }
+
+    /// Zero a load cell with no load applied
+    ///
+    /// Averages the bridge output over a full stability window of readings.
+    pub fn tare(&mut self, cell: &mut LoadCell) -> Result<(), AdcError> {
+        let volts = self.read_bridge(cell, cell.samples * cell.window)?;
+        cell.set_zero(volts);
+        Ok(())
+    }
+
+    /// Calibrate the span of a tared load cell with a known mass applied
+    ///
+    /// # Arguments
+    /// * `cell` - Tared load cell
+    /// * `mass` - Applied mass in the load cell's unit
+    pub fn calibrate_load_cell(&mut self, cell: &mut LoadCell, mass: f32) -> Result<(), AdcError> {
+        let volts = self.read_bridge(cell, cell.samples * cell.window)?;
+        cell.set_span(volts, mass)
+    }
+
+    /// Take a weight reading
+    pub fn read_weight(&mut self, cell: &mut LoadCell) -> Result<WeightReading, AdcError> {
+        let volts = self.read_bridge(cell, cell.samples)?;
+        cell.push_volts(volts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cell reading 1 mV per kilogram with a 0.1 mV offset
+    fn calibrated() -> LoadCell {
+        let mut cell = LoadCell::new().with_stability(2.0, 4);
+        cell.set_zero(0.0001);
+        cell.set_span(0.0001 + 0.001, 1000.0).unwrap();
+        cell
+    }
+
+    #[test]
+    fn test_uncalibrated_cell() {
+        let mut cell = LoadCell::new();
+        assert!(matches!(cell.push_volts(0.001), Err(AdcError::InvalidCalibration(_))));
+        assert!(cell.set_span(0.0, 100.0).is_err(), "Span equal to the tare");
+    }
+
+    #[test]
+    fn test_tare_and_span() {
+        let mut cell = calibrated();
+        assert!((cell.grams_per_volt().unwrap() - 1_000_000.0).abs() < 1.0);
+
+        let reading = cell.push_volts(0.0001 + 0.0005).unwrap();
+        assert!((reading.weight - 500.0).abs() < 0.01);
+        assert_eq!(reading.unit, WeightUnit::Grams);
+        assert!(!reading.stable, "A single reading is not stable");
+    }
+
+    #[test]
+    fn test_units() {
+        let mut cell = calibrated().with_unit(WeightUnit::Kilograms);
+        assert!((cell.push_volts(0.0021).unwrap().weight - 2.0).abs() < 1e-4);
+
+        let mut cell = calibrated().with_unit(WeightUnit::Pounds);
+        assert!((cell.push_volts(0.0001 + 0.000_453_592_37).unwrap().weight - 1.0).abs() < 1e-3);
+        assert_eq!(WeightUnit::Ounces.symbol(), "oz");
+    }
+
+    #[test]
+    fn test_stability_and_creep() {
+        let mut cell = calibrated();
+        let mut readings = Vec::new();
+        // 500 g load creeping up by 0.2 g per reading
+        for i in 0..8 {
+            readings.push(cell.push_volts(0.0001 + (500.0 + 0.2 * i as f32) * 1e-6).unwrap());
+        }
+        assert!(!readings[2].stable);
+        assert!(readings[3].stable, "Window is full and within tolerance");
+        assert!(readings[7].stable, "Slow creep stays stable");
+        assert!((readings[7].weight - 501.1).abs() < 0.01, "Average follows creep");
+
+        // A new load restarts the window instead of being averaged with the old one
+        let reading = cell.push_volts(0.0001 + 800e-6).unwrap();
+        assert!((reading.weight - 800.0).abs() < 0.01);
+        assert!(!reading.stable);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_load_cell_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::new("ADS1115".to_string()));
+
+        // Unloaded cell: after taring the weight is only noise
+        let mut cell = LoadCell::new().with_calibration(0.0, 1_000_000.0);
+        adc.tare(&mut cell).expect("Failed to tare");
+        for _ in 0..20 {
+            let reading = adc.read_weight(&mut cell).expect("Failed to read");
+            assert_eq!(reading.unit, WeightUnit::Grams);
+            assert!(reading.volts.abs() <= 0.256, "Bridge output {} V is outside the range", reading.volts);
+            assert!(reading.weight.abs() < 100.0, "Unloaded cell weighs {} g after tare", reading.weight);
+        }
+    }
+}
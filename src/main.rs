@@ -1,8 +1,6 @@
 extern crate qwiic_adc_rs;
 
 use qwiic_adc_rs::*;
-use std::thread;
-use std::time::Duration;
 
 fn main() {
     // Create configuration with custom timing for hardware compatibility
@@ -84,22 +82,22 @@ fn main() {
     println!("  Low threshold: 1000");
     println!("  High threshold: 3000");
 
-    // Demonstrate continuous mode
+    // Demonstrate continuous mode, with reads paced to the data rate
     println!("\nContinuous mode on channel 0 (5 readings):");
-    adc.start_continuous(0).expect("Failed to start continuous mode");
-    
-    for i in 0..5 {
-        thread::sleep(Duration::from_millis(10));
-        match adc.read_last_conversion() {
-            Ok(value) => {
-                let voltage = adc.raw_to_voltage(value, PGA::Two);
-                println!("  Reading {}: {value} (raw) = {voltage:.2} mV", i + 1);
+    let mut stream = adc.stream(0, SampleRates::S1600Hz).expect("Failed to start continuous mode");
+
+    for (i, sample) in stream.by_ref().take(5).enumerate() {
+        match sample {
+            Ok(sample) => {
+                println!("  Reading {}: {} (raw) = {:.4} V [conversion #{}]",
+                    i + 1, sample.value, sample.volts, sample.seq);
             },
             Err(e) => println!("  Reading {}: Error - {e:?}", i + 1),
         }
     }
-    
-    adc.stop_continuous().expect("Failed to stop continuous mode");
+
+    println!("Missed conversions: {}", stream.missed());
+    drop(stream);
     println!("Continuous mode stopped");
 
     // Test different gain settings
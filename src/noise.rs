@@ -0,0 +1,261 @@
+//! Noise characterisation
+//!
+//! Measures the noise of an input for every gain and data rate setting, to
+//! choose settings from the noise of the actual board rather than the
+//! datasheet. Each setting is streamed in continuous mode and summarised as
+//! RMS and peak-to-peak noise, effective number of bits (full-scale range over
+//! RMS noise) and noise-free bits (full-scale range over peak-to-peak noise).
+//! Reports print as a text table or as JSON.
+
+use std::fmt::Write;
+
+use crate::{AdcError, Mux, QwiicADC, SampleRates, Saturation, PGA};
+
+/// Noise of one gain and data rate setting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseResult {
+    /// PGA gain setting
+    pub gain: PGA,
+    /// Data rate setting
+    pub rate: SampleRates,
+    /// Conversions per second of the data rate on this chip
+    pub rate_hz: f32,
+    /// Mean of the samples in volts
+    pub mean_volts: f32,
+    /// RMS noise (standard deviation) in volts
+    pub rms_noise_volts: f32,
+    /// Peak-to-peak noise in volts
+    pub peak_to_peak_volts: f32,
+    /// Effective number of bits
+    pub effective_bits: f32,
+    /// Noise-free bits
+    pub noise_free_bits: f32,
+    /// Whether any sample was a full-scale code
+    pub saturated: bool,
+    /// Number of samples taken
+    pub samples: usize,
+}
+
+impl NoiseResult {
+    /// Summarise the samples of one setting
+    ///
+    /// # Arguments
+    /// * `counts` - Signed conversion results
+    /// * `full_scale_counts` - Counts at positive full scale
+    /// * `resolution_bits` - Resolution of the chip, the limit for both bit figures
+    pub(crate) fn from_counts(gain: PGA, rate: SampleRates, rate_hz: f32, counts: &[i16], full_scale_counts: f32, resolution_bits: f32) -> NoiseResult {
+        let n = counts.len().max(1) as f64;
+        let mean = counts.iter().map(|&c| c as f64).sum::<f64>() / n;
+        let variance = counts.iter().map(|&c| (c as f64 - mean).powi(2)).sum::<f64>() / n;
+        let rms = variance.sqrt() as f32;
+        let min = counts.iter().copied().min().unwrap_or(0);
+        let max = counts.iter().copied().max().unwrap_or(0);
+        let peak_to_peak = (max as f32 - min as f32).max(0.0);
+
+        // Noise below one count is limited by the resolution
+        let bits = |noise: f32| if noise > 0.0 {
+            (2.0 * full_scale_counts / noise).log2().min(resolution_bits)
+        } else {
+            resolution_bits
+        };
+        let volts_per_count = gain.full_scale_mv() / 1000.0 / full_scale_counts;
+        NoiseResult {
+            gain,
+            rate,
+            rate_hz,
+            mean_volts: mean as f32 * volts_per_count,
+            rms_noise_volts: rms * volts_per_count,
+            peak_to_peak_volts: peak_to_peak * volts_per_count,
+            effective_bits: bits(rms),
+            noise_free_bits: bits(peak_to_peak),
+            saturated: counts.iter().any(|&c| Saturation::from_counts(c, full_scale_counts).is_saturated()),
+            samples: counts.len(),
+        }
+    }
+}
+
+/// Noise of an input across gain and data rate settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseReport {
+    /// Input measured
+    pub input: Mux,
+    /// One result per setting, by gain from the widest range and then by data rate
+    pub results: Vec<NoiseResult>,
+}
+
+impl NoiseReport {
+    /// Recommend the setting with the lowest RMS noise in volts
+    ///
+    /// Only settings at or above `min_rate_hz` whose range holds the measured
+    /// signal with 10% headroom, and that did not saturate, are considered. Of
+    /// equally quiet settings the faster one is preferred.
+    pub fn recommended(&self, min_rate_hz: f32) -> Option<&NoiseResult> {
+        self.results.iter()
+            .filter(|result| result.rate_hz >= min_rate_hz && !result.saturated)
+            .filter(|result| result.mean_volts.abs() <= 0.9 * result.gain.full_scale_mv() / 1000.0)
+            .min_by(|a, b| a.rms_noise_volts.total_cmp(&b.rms_noise_volts)
+                .then(b.rate_hz.total_cmp(&a.rate_hz)))
+    }
+
+    /// Format the report as a text table, noise in microvolts
+    pub fn to_table(&self) -> String {
+        let mut table = format!("{:>9} {:>8} {:>12} {:>12} {:>12} {:>6} {:>6}\n",
+            "Range", "SPS", "Mean (V)", "RMS (uV)", "P-P (uV)", "ENOB", "NFB");
+        for result in &self.results {
+            let _ = writeln!(table, "{:>9} {:>8} {:>12.6} {:>12.2} {:>12.2} {:>6.2} {:>6.2}{}",
+                format!("±{:.3}V", result.gain.full_scale_mv() / 1000.0),
+                result.rate_hz,
+                result.mean_volts,
+                result.rms_noise_volts * 1e6,
+                result.peak_to_peak_volts * 1e6,
+                result.effective_bits,
+                result.noise_free_bits,
+                if result.saturated { " saturated" } else { "" });
+        }
+        table
+    }
+
+    /// Format the report as JSON, noise in volts
+    pub fn to_json(&self) -> String {
+        let results: Vec<String> = self.results.iter()
+            .map(|result| format!(
+                "{{\"gain\":\"{:?}\",\"full_scale_volts\":{},\"rate\":\"{:?}\",\"rate_hz\":{},\"samples\":{},\
+                 \"mean_volts\":{},\"rms_noise_volts\":{},\"peak_to_peak_volts\":{},\
+                 \"effective_bits\":{},\"noise_free_bits\":{},\"saturated\":{}}}",
+                result.gain, result.gain.full_scale_mv() / 1000.0, result.rate, result.rate_hz, result.samples,
+                result.mean_volts, result.rms_noise_volts, result.peak_to_peak_volts,
+                result.effective_bits, result.noise_free_bits, result.saturated))
+            .collect();
+        format!("{{\"input\":\"{:?}\",\"results\":[{}]}}", self.input, results.join(","))
+    }
+}
+
+impl QwiicADC {
+    /// Measure the noise of an input at every gain and data rate setting
+    ///
+    /// The input should carry a steady signal, e.g. be shorted or connected to
+    /// a reference. Every data rate code is measured; on the ADS1015, where the
+    /// two fastest codes both give 3300 SPS, that rate is measured once. Slow
+    /// rates take a while: 256 samples at 8 SPS is 32 s. Saturated samples are
+    /// counted in `clipping` like any other streamed conversion.
+    ///
+    /// # Arguments
+    /// * `input` - Input to measure
+    /// * `samples` - Conversions taken at each setting
+    pub fn characterize_noise(&mut self, input: Mux, samples: usize) -> Result<NoiseReport, AdcError> {
+        let samples = samples.max(2);
+        let resolution_bits = (2.0 * self.full_scale_counts()).log2();
+        let mut results = Vec::with_capacity(PGA::RANGES.len() * SampleRates::RATES.len());
+
+        for &gain in PGA::RANGES.iter() {
+            let mut measured: Vec<f32> = Vec::with_capacity(SampleRates::RATES.len());
+            for &rate in SampleRates::RATES.iter() {
+                let rate_hz = self.data_rate_hz(rate);
+                if measured.contains(&rate_hz) {
+                    continue;
+                }
+                measured.push(rate_hz);
+
+                // The first conversion may straddle the configuration change
+                let mut counts = Vec::with_capacity(samples);
+                for sample in self.stream_input(input, gain, rate)?.skip(1).take(samples) {
+                    counts.push(sample?.value);
+                }
+                let counts: Vec<i16> = counts.into_iter().map(|raw| self.raw_to_signed(raw)).collect();
+                results.push(NoiseResult::from_counts(gain, rate, rate_hz, &counts,
+                    self.full_scale_counts(), resolution_bits));
+            }
+        }
+        Ok(NoiseReport { input, results })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(gain: PGA, rate_hz: f32, counts: &[i16]) -> NoiseResult {
+        NoiseResult::from_counts(gain, SampleRates::S128Hz, rate_hz, counts, 32768.0, 16.0)
+    }
+
+    #[test]
+    fn test_noise_figures() {
+        // Alternating ±2 counts: RMS 2, peak-to-peak 4
+        let counts: Vec<i16> = (0..100).map(|i| if i % 2 == 0 { 102 } else { 98 }).collect();
+        let result = result(PGA::Two, 128.0, &counts);
+        assert!((result.mean_volts - 100.0 * 0.0000625).abs() < 1e-7);
+        assert!((result.rms_noise_volts - 2.0 * 0.0000625).abs() < 1e-8);
+        assert!((result.peak_to_peak_volts - 4.0 * 0.0000625).abs() < 1e-8);
+        assert!((result.effective_bits - 15.0).abs() < 1e-4);
+        assert!((result.noise_free_bits - 14.0).abs() < 1e-4);
+        assert!(!result.saturated);
+        assert_eq!(result.samples, 100);
+    }
+
+    #[test]
+    fn test_noiseless_and_saturated() {
+        let quiet = result(PGA::Two, 128.0, &[5, 5, 5, 5]);
+        assert_eq!(quiet.effective_bits, 16.0);
+        assert_eq!(quiet.noise_free_bits, 16.0);
+
+        assert!(result(PGA::Sixteen, 128.0, &[32767, 32767, 32766]).saturated);
+        assert!(result(PGA::Sixteen, 128.0, &[-32768, -32700]).saturated);
+
+        let ads1015 = NoiseResult::from_counts(PGA::Two, SampleRates::S1600Hz, 1600.0, &[2047, 2040], 2048.0, 12.0);
+        assert!(ads1015.saturated);
+        assert!(ads1015.noise_free_bits <= 12.0);
+    }
+
+    #[test]
+    fn test_recommendation() {
+        let report = NoiseReport {
+            input: Mux::Single0,
+            results: vec![
+                result(PGA::One, 128.0, &[8000, 8004, 7996]),
+                result(PGA::Two, 128.0, &[16000, 16002, 15998]),
+                result(PGA::Two, 860.0, &[16000, 16002, 15998]),
+                result(PGA::Two, 8.0, &[16000, 16000, 16001]),
+                // 1 V does not fit the ±0.512 V range
+                result(PGA::Eight, 128.0, &[32767, 32767, 32767]),
+            ],
+        };
+
+        let best = report.recommended(0.0).unwrap();
+        assert_eq!((best.gain, best.rate_hz), (PGA::Two, 8.0));
+        let best = report.recommended(100.0).unwrap();
+        assert_eq!((best.gain, best.rate_hz), (PGA::Two, 860.0), "Faster of equally quiet settings");
+        assert!(report.recommended(1000.0).is_none());
+    }
+
+    #[test]
+    fn test_output_formats() {
+        let report = NoiseReport { input: Mux::DiffP0N1, results: vec![result(PGA::Four, 128.0, &[10, 12])] };
+
+        let table = report.to_table();
+        assert_eq!(table.lines().count(), 2);
+        assert!(table.contains("±1.024V"));
+
+        let json = report.to_json();
+        assert!(json.starts_with("{\"input\":\"DiffP0N1\",\"results\":[{\"gain\":\"Four\""));
+        assert!(json.contains("\"rate_hz\":128,"));
+        assert!(json.contains("\"saturated\":false}]}"));
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_noise_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::new("ADS1115".to_string()));
+
+        // Shorted input: every setting measures, and the quietest is usable
+        let report = adc.characterize_noise(Mux::DiffP0N1, 64).expect("Failed to characterise");
+        assert_eq!(report.input, Mux::DiffP0N1);
+        assert_eq!(report.results.len(), PGA::RANGES.len() * SampleRates::RATES.len());
+        for result in &report.results {
+            assert_eq!(result.samples, 64);
+            assert!(result.effective_bits <= 16.0);
+            assert!(result.noise_free_bits <= result.effective_bits);
+        }
+        assert!(report.recommended(0.0).is_some());
+        assert_eq!(report.to_table().lines().count(), report.results.len() + 1);
+    }
+}
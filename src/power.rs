@@ -0,0 +1,391 @@
+//! AC current and power measurement
+//!
+//! A [`PowerMeter`] captures a window of a current transformer's burden
+//! voltage at a high data rate, removes the DC bias and computes the true RMS
+//! primary current. With a voltage channel configured, current and voltage are
+//! sampled alternately; the voltage is interpolated to the instant of each
+//! current sample before computing real power, so the delay between the two
+//! conversions does not appear as a phase shift. Every switch waits out the
+//! conversion in flight, so in that mode each input is sampled at under a
+//! quarter of the data rate. Real power is integrated into an energy total
+//! across readings.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{AdcError, Mux, QwiicADC, SampleRates, PGA};
+
+/// Current transformer with a burden resistor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CurrentTransformer {
+    /// Input across the burden resistor (default: `Mux::DiffP0N1`)
+    pub input: Mux,
+    /// Turns ratio, primary to secondary, e.g. 2000 for a 100 A : 50 mA CT
+    pub ratio: f32,
+    /// Burden resistance in ohms
+    pub burden_ohms: f32,
+    /// PGA gain setting (default: `PGA::Two`)
+    pub gain: PGA,
+    /// Data rate (default: the fastest)
+    pub rate: SampleRates,
+}
+
+impl CurrentTransformer {
+    /// Create a current transformer on `Mux::DiffP0N1`
+    ///
+    /// # Arguments
+    /// * `ratio` - Turns ratio, primary to secondary
+    /// * `burden_ohms` - Burden resistance in ohms
+    pub fn new(ratio: f32, burden_ohms: f32) -> CurrentTransformer {
+        CurrentTransformer {
+            input: Mux::DiffP0N1,
+            ratio,
+            burden_ohms,
+            gain: PGA::Two,
+            rate: SampleRates::S3300Hz,
+        }
+    }
+
+    /// Set the input across the burden resistor
+    pub fn with_input(mut self, input: Mux) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// Set the PGA gain setting
+    pub fn with_gain(mut self, gain: PGA) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Set the data rate
+    pub fn with_sample_rate(mut self, rate: SampleRates) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Primary current in amps for a burden voltage
+    pub fn amps(&self, volts: f32) -> f32 {
+        volts / self.burden_ohms * self.ratio
+    }
+}
+
+/// Mains voltage sensor, e.g. an AC transformer and divider
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoltageSensor {
+    /// Input of the sensor
+    pub input: Mux,
+    /// Mains volts per volt at the input
+    pub scale: f32,
+    /// PGA gain setting (default: `PGA::Two`)
+    pub gain: PGA,
+}
+
+impl VoltageSensor {
+    /// Create a voltage sensor
+    ///
+    /// # Arguments
+    /// * `input` - Input of the sensor
+    /// * `scale` - Mains volts per volt at the input
+    pub fn new(input: Mux, scale: f32) -> VoltageSensor {
+        VoltageSensor { input, scale, gain: PGA::Two }
+    }
+
+    /// Set the PGA gain setting
+    pub fn with_gain(mut self, gain: PGA) -> Self {
+        self.gain = gain;
+        self
+    }
+}
+
+/// Result of one power measurement window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerReading {
+    /// True RMS primary current in amps
+    pub current_rms: f32,
+    /// True RMS mains voltage, if a voltage sensor is configured
+    pub voltage_rms: Option<f32>,
+    /// Real power in watts
+    pub real_power: Option<f32>,
+    /// Apparent power in volt-amperes
+    pub apparent_power: Option<f32>,
+    /// Real over apparent power
+    pub power_factor: Option<f32>,
+    /// Energy accumulated by the meter, in watt-hours
+    pub energy_wh: f64,
+    /// Number of current samples in the window
+    pub samples: usize,
+}
+
+/// Current and power meter with energy accumulation
+#[derive(Debug, Clone)]
+pub struct PowerMeter {
+    /// Current transformer
+    pub ct: CurrentTransformer,
+    /// Optional voltage sensor for power measurement
+    pub voltage: Option<VoltageSensor>,
+    /// Length of each capture; a whole number of mains cycles is best (default: 200 ms)
+    pub window: Duration,
+    energy_wh: f64,
+    last_reading: Option<Instant>,
+}
+
+impl PowerMeter {
+    /// Create a current-only meter
+    pub fn new(ct: CurrentTransformer) -> PowerMeter {
+        PowerMeter {
+            ct,
+            voltage: None,
+            window: Duration::from_millis(200),
+            energy_wh: 0.0,
+            last_reading: None,
+        }
+    }
+
+    /// Add a voltage sensor for power and energy measurement
+    ///
+    /// Current and voltage are then sampled alternately, each at about the data
+    /// rate divided by 4.4 (e.g. 360 SPS per input at 1600 SPS).
+    pub fn with_voltage(mut self, voltage: VoltageSensor) -> Self {
+        self.voltage = Some(voltage);
+        self
+    }
+
+    /// Set the capture window length
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Energy accumulated so far in watt-hours
+    pub fn energy_wh(&self) -> f64 {
+        self.energy_wh
+    }
+
+    /// Reset the energy total
+    pub fn reset_energy(&mut self) {
+        self.energy_wh = 0.0;
+        self.last_reading = None;
+    }
+
+    /// Compute a reading from captured burden and sensor voltages
+    ///
+    /// Energy is accumulated assuming the real power was constant since the
+    /// previous reading (or over the window, for the first reading).
+    ///
+    /// # Arguments
+    /// * `current` - (seconds, volts) samples of the burden voltage
+    /// * `voltage` - (seconds, volts) samples of the voltage sensor input, on the same time base
+    /// * `now` - Time of the reading
+    pub fn process(&mut self, current: &[(f32, f32)], voltage: Option<&[(f32, f32)]>, now: Instant) -> PowerReading {
+        let current_offset = mean(current);
+        let amps: Vec<(f32, f32)> = current.iter()
+            .map(|&(t, v)| (t, self.ct.amps(v - current_offset)))
+            .collect();
+        let current_rms = rms(amps.iter().map(|&(_, a)| a));
+
+        let mut reading = PowerReading {
+            current_rms,
+            voltage_rms: None,
+            real_power: None,
+            apparent_power: None,
+            power_factor: None,
+            energy_wh: self.energy_wh,
+            samples: current.len(),
+        };
+
+        if let (Some(sensor), Some(voltage)) = (self.voltage, voltage) {
+            let voltage_offset = mean(voltage);
+            let mains: Vec<(f32, f32)> = voltage.iter()
+                .map(|&(t, v)| (t, (v - voltage_offset) * sensor.scale))
+                .collect();
+            let voltage_rms = rms(mains.iter().map(|&(_, v)| v));
+
+            // Instantaneous power at each current sample inside the voltage capture
+            let products: Vec<f32> = amps.iter()
+                .filter_map(|&(t, a)| interpolate(&mains, t).map(|v| v * a))
+                .collect();
+            let real_power = products.iter().sum::<f32>() / products.len().max(1) as f32;
+            let apparent_power = voltage_rms * current_rms;
+
+            let hours = match self.last_reading {
+                Some(last) => now.saturating_duration_since(last).as_secs_f64(),
+                None => self.window.as_secs_f64(),
+            } / 3600.0;
+            self.energy_wh += real_power as f64 * hours;
+
+            reading.voltage_rms = Some(voltage_rms);
+            reading.real_power = Some(real_power);
+            reading.apparent_power = Some(apparent_power);
+            reading.power_factor = Some(if apparent_power > 0.0 { real_power / apparent_power } else { 0.0 });
+            reading.energy_wh = self.energy_wh;
+        }
+        self.last_reading = Some(now);
+        reading
+    }
+}
+
+/// Mean of the values of (time, value) samples
+fn mean(samples: &[(f32, f32)]) -> f32 {
+    samples.iter().map(|&(_, v)| v).sum::<f32>() / samples.len().max(1) as f32
+}
+
+/// Root mean square of values
+fn rms<I: Iterator<Item = f32>>(values: I) -> f32 {
+    let (sum, count) = values.fold((0.0f64, 0usize), |(sum, count), v| (sum + (v as f64).powi(2), count + 1));
+    (sum / count.max(1) as f64).sqrt() as f32
+}
+
+/// Linearly interpolate (time, value) samples sorted by time at `t`
+fn interpolate(samples: &[(f32, f32)], t: f32) -> Option<f32> {
+    let index = samples.iter().position(|&(time, _)| time >= t)?;
+    let (t1, v1) = samples[index];
+    if t1 == t {
+        return Some(v1);
+    }
+    let (t0, v0) = samples[index.checked_sub(1)?];
+    Some(v0 + (v1 - v0) * (t - t0) / (t1 - t0))
+}
+
+impl QwiicADC {
+    /// Capture one window and compute current, and power if a voltage sensor is configured
+    ///
+    /// Current alone is streamed at the CT's data rate. With a voltage sensor,
+    /// each conversion follows a reconfiguration and a settling time of 2.2
+    /// conversion periods, so each input is sampled at about the data rate
+    /// divided by 4.4, less the I2C transfer time; `PowerReading::samples`
+    /// gives the number actually taken.
+    pub fn read_power(&mut self, meter: &mut PowerMeter) -> Result<PowerReading, AdcError> {
+        let ct = meter.ct;
+        let start = Instant::now();
+        let mut current = Vec::new();
+        let mut voltage = Vec::new();
+
+        match meter.voltage {
+            None => {
+                for sample in self.stream_input(ct.input, ct.gain, ct.rate)? {
+                    let sample = sample?;
+                    current.push((sample.timestamp.saturating_duration_since(start).as_secs_f32(), sample.volts));
+                    if sample.timestamp.saturating_duration_since(start) >= meter.window {
+                        break;
+                    }
+                }
+            },
+            Some(sensor) => {
+                // Alternate between the inputs, discarding the conversion in flight at each switch
+                let settle = self.settling_time(ct.rate as u16);
+                while start.elapsed() < meter.window {
+                    for (input, gain, samples) in [(ct.input, ct.gain, &mut current), (sensor.input, sensor.gain, &mut voltage)] {
+                        self.start_continuous_input(input, gain, ct.rate)?;
+                        thread::sleep(settle);
+                        let raw = self.read_last_conversion()?;
+                        self.count_clipping(input, raw);
+                        let volts = self.counts_to_volts(self.raw_to_signed(raw) as f32, gain);
+                        samples.push((start.elapsed().as_secs_f32(), volts));
+                    }
+                }
+                self.stop_continuous()?;
+            },
+        }
+
+        Ok(meter.process(&current, meter.voltage.map(|_| voltage.as_slice()), Instant::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    /// Sine samples of `amplitude` on `offset`, at times `t0 + k * dt`
+    fn sine(amplitude: f32, offset: f32, phase: f32, t0: f32, dt: f32, n: usize) -> Vec<(f32, f32)> {
+        (0..n).map(|k| {
+            let t = t0 + k as f32 * dt;
+            (t, offset + amplitude * (2.0 * PI * 50.0 * t + phase).sin())
+        }).collect()
+    }
+
+    #[test]
+    fn test_current_rms_removes_bias() {
+        // 2000:1 CT into 100 ohms: 1 V peak is 20 A peak
+        let mut meter = PowerMeter::new(CurrentTransformer::new(2000.0, 100.0));
+        let current = sine(1.0, 1.65, 0.0, 0.0, 1.0 / 3300.0, 660);
+        let reading = meter.process(&current, None, Instant::now());
+
+        assert!((reading.current_rms - 20.0 / 2.0f32.sqrt()).abs() < 0.01, "RMS was {}", reading.current_rms);
+        assert_eq!(reading.real_power, None);
+        assert_eq!(reading.samples, 660);
+    }
+
+    #[test]
+    fn test_power_with_phase_shift() {
+        let ct = CurrentTransformer::new(2000.0, 100.0);
+        let mut meter = PowerMeter::new(ct)
+            .with_voltage(VoltageSensor::new(Mux::Single2, 230.0 * 2.0f32.sqrt()));
+
+        // Alternating conversions 0.5 ms apart; current lags voltage by 60 degrees
+        let dt = 1.0 / 1000.0;
+        let voltage = sine(1.0, 1.0, 0.0, 0.0, dt, 200);
+        let current = sine(0.5, 0.0, -PI / 3.0, dt / 2.0, dt, 200);
+        let now = Instant::now();
+        let reading = meter.process(&current, Some(&voltage), now);
+
+        let irms = 10.0 / 2.0f32.sqrt();
+        assert!((reading.voltage_rms.unwrap() - 230.0).abs() < 0.5);
+        assert!((reading.current_rms - irms).abs() < 0.02);
+        assert!((reading.apparent_power.unwrap() - 230.0 * irms).abs() < 5.0);
+        assert!((reading.power_factor.unwrap() - 0.5).abs() < 0.01, "PF was {:?}", reading.power_factor);
+
+        // The first reading accumulates over the window
+        let expected_wh = reading.real_power.unwrap() as f64 * 0.2 / 3600.0;
+        assert!((meter.energy_wh() - expected_wh).abs() < 1e-6);
+
+        // Later readings accumulate over the time since the previous one
+        meter.process(&current, Some(&voltage), now + Duration::from_secs(3600));
+        assert!((meter.energy_wh() - expected_wh - reading.real_power.unwrap() as f64).abs() < 0.5);
+
+        meter.reset_energy();
+        assert_eq!(meter.energy_wh(), 0.0);
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let samples = [(0.0, 0.0), (1.0, 10.0), (2.0, 0.0)];
+        assert_eq!(interpolate(&samples, 0.5), Some(5.0));
+        assert_eq!(interpolate(&samples, 1.0), Some(10.0));
+        assert_eq!(interpolate(&samples, 0.0), Some(0.0));
+        assert_eq!(interpolate(&samples, -1.0), None);
+        assert_eq!(interpolate(&samples, 3.0), None);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_power_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+
+        let mut meter = PowerMeter::new(CurrentTransformer::new(2000.0, 33.0))
+            .with_voltage(VoltageSensor::new(Mux::Single2, 200.0));
+        let mut energy_wh = 0.0;
+        let mut last: Option<Instant> = None;
+        for _ in 0..5 {
+            let reading = adc.read_power(&mut meter).expect("Failed to read");
+            let now = Instant::now();
+            assert!(reading.samples > 0);
+            assert!(reading.current_rms >= 0.0);
+            assert!(reading.voltage_rms.is_some(), "Voltage sensor configured");
+            let power_factor = reading.power_factor.expect("Voltage sensor configured");
+            assert!((-1.0..=1.0).contains(&power_factor), "Power factor {}", power_factor);
+
+            // Energy changes by the real power, which may be negative, times the
+            // time since the previous reading
+            let power = reading.real_power.expect("Voltage sensor configured") as f64;
+            let seconds = last.map_or(meter.window, |last| now - last).as_secs_f64();
+            let expected = power * seconds / 3600.0;
+            let tolerance = power.abs() * 0.01 / 3600.0 + 1e-12;
+            assert!((reading.energy_wh - energy_wh - expected).abs() <= tolerance,
+                "Energy changed by {} Wh, expected {} Wh", reading.energy_wh - energy_wh, expected);
+            energy_wh = reading.energy_wh;
+            last = Some(now);
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! pH and ORP probes
+//!
+//! A [`PhProbe`] reads a pH amplifier board and converts its output with a
+//! buffer calibration. The electrode follows the Nernst equation, so its slope
+//! is proportional to absolute temperature; readings are compensated with a
+//! fixed temperature or a thermistor on another channel. One buffer corrects
+//! the offset only, two or more fit offset and slope. An [`OrpProbe`] reports
+//! its potential in millivolts, corrected by a single standard solution.
+//!
+//! A bare electrode is bipolar, about ±59 mV per pH around pH 7, so it needs an
+//! amplifier board with an offset output, or a differential input with the
+//! reference electrode held at mid-supply; on a single-ended input everything
+//! above pH 7 clips at zero.
+//!
+//! Probe calibrations are kept in the configuration's [`crate::CalibrationSet`]
+//! and are saved and loaded with the channel corrections.
+
+use crate::{AdcError, Mux, QwiicADC, SampleRates, TemperatureUnit, Thermistor, PGA};
+
+/// Temperature of the calibration reference in kelvin
+const REFERENCE_KELVIN: f32 = 298.15;
+
+/// Ideal electrode slope at 25 °C in volts per pH
+pub const NERNST_SLOPE: f32 = -0.059_16;
+
+/// A buffer measurement used for pH calibration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BufferPoint {
+    /// Probe output in volts
+    pub volts: f32,
+    /// pH of the buffer at its temperature
+    pub ph: f32,
+    /// Buffer temperature in degrees Celsius
+    pub celsius: f32,
+}
+
+/// pH probe calibration, referred to 25 °C
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhCalibration {
+    /// Probe output at pH 7 in volts
+    pub offset: f32,
+    /// Change of the probe output per pH at 25 °C in volts
+    pub slope: f32,
+}
+
+impl PhCalibration {
+    /// Fit a calibration to buffer measurements
+    ///
+    /// # Arguments
+    /// * `points` - One or more buffer measurements
+    /// * `nominal_slope` - Slope in volts per pH assumed for a single buffer
+    ///
+    /// # Returns
+    /// * `Some(calibration)` - The fitted calibration
+    /// * `None` if there are no points or the buffers do not differ in pH
+    pub fn from_points(points: &[BufferPoint], nominal_slope: f32) -> Option<PhCalibration> {
+        // The output is offset + slope * x with x the temperature-scaled pH difference
+        let x = |point: &BufferPoint| Self::temperature_factor(point.celsius) * (point.ph - 7.0);
+        let calibration = match points {
+            [] => return None,
+            [point] => PhCalibration { offset: point.volts - nominal_slope * x(point), slope: nominal_slope },
+            _ => {
+                let n = points.len() as f32;
+                let mean_x = points.iter().map(x).sum::<f32>() / n;
+                let mean_y = points.iter().map(|point| point.volts).sum::<f32>() / n;
+                let sxx: f32 = points.iter().map(|point| (x(point) - mean_x).powi(2)).sum();
+                let sxy: f32 = points.iter().map(|point| (x(point) - mean_x) * (point.volts - mean_y)).sum();
+                if sxx < 1e-6 {
+                    return None;
+                }
+                let slope = sxy / sxx;
+                PhCalibration { offset: mean_y - slope * mean_x, slope }
+            },
+        };
+        if calibration.offset.is_finite() && calibration.slope.is_finite() && calibration.slope != 0.0 {
+            Some(calibration)
+        } else {
+            None
+        }
+    }
+
+    /// Ratio of the electrode slope at a temperature to the slope at 25 °C
+    fn temperature_factor(celsius: f32) -> f32 {
+        TemperatureUnit::Celsius.to_kelvin(celsius) / REFERENCE_KELVIN
+    }
+
+    /// Convert a probe output to pH
+    ///
+    /// # Arguments
+    /// * `volts` - Probe output
+    /// * `celsius` - Solution temperature in degrees Celsius
+    pub fn ph(&self, volts: f32, celsius: f32) -> f32 {
+        7.0 + (volts - self.offset) / (self.slope * Self::temperature_factor(celsius))
+    }
+
+    /// Slope at 25 °C in millivolts per pH
+    pub fn slope_mv(&self) -> f32 {
+        self.slope * 1000.0
+    }
+
+    /// Slope as a percentage of a nominal slope, a measure of electrode condition
+    pub fn efficiency(&self, nominal_slope: f32) -> f32 {
+        self.slope / nominal_slope * 100.0
+    }
+}
+
+/// Source of the solution temperature
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProbeTemperature {
+    /// Fixed temperature in degrees Celsius
+    Fixed(f32),
+    /// Thermistor on a single-ended channel, read with `read_thermistor`
+    Thermistor {
+        /// Channel number (must be 0-3)
+        channel: u8,
+        /// Thermistor settings
+        thermistor: Thermistor,
+    },
+}
+
+/// A pH reading
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhReading {
+    /// Temperature-compensated pH
+    pub ph: f32,
+    /// Probe output in volts
+    pub volts: f32,
+    /// Solution temperature in degrees Celsius, or `None` if the thermistor
+    /// could not be read and 25 °C was assumed
+    pub celsius: Option<f32>,
+}
+
+/// pH probe settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhProbe {
+    /// Input of the amplifier output
+    pub input: Mux,
+    /// PGA gain setting (default: `PGA::One`)
+    pub gain: PGA,
+    /// Data rate (default: the slowest)
+    pub rate: SampleRates,
+    /// Solution temperature (default: fixed at 25 °C)
+    pub temperature: ProbeTemperature,
+    /// Slope assumed for single-buffer calibration in volts per pH (default: `NERNST_SLOPE`)
+    pub nominal_slope: f32,
+}
+
+impl PhProbe {
+    /// Create a pH probe on an input
+    pub fn new(input: Mux) -> PhProbe {
+        PhProbe {
+            input,
+            gain: PGA::One,
+            rate: SampleRates::S128Hz,
+            temperature: ProbeTemperature::Fixed(25.0),
+            nominal_slope: NERNST_SLOPE,
+        }
+    }
+
+    /// Set the PGA gain setting
+    pub fn with_gain(mut self, gain: PGA) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Set the data rate
+    pub fn with_sample_rate(mut self, rate: SampleRates) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Set the source of the solution temperature
+    pub fn with_temperature(mut self, temperature: ProbeTemperature) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    /// Set the slope assumed for single-buffer calibration, e.g. the
+    /// electrode slope times the amplifier gain
+    pub fn with_nominal_slope(mut self, slope: f32) -> Self {
+        self.nominal_slope = slope;
+        self
+    }
+}
+
+/// ORP probe settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrpProbe {
+    /// Input of the amplifier output
+    pub input: Mux,
+    /// PGA gain setting (default: `PGA::Two`)
+    pub gain: PGA,
+    /// Data rate (default: the slowest)
+    pub rate: SampleRates,
+}
+
+impl OrpProbe {
+    /// Create an ORP probe on an input
+    pub fn new(input: Mux) -> OrpProbe {
+        OrpProbe { input, gain: PGA::Two, rate: SampleRates::S128Hz }
+    }
+
+    /// Set the PGA gain setting
+    pub fn with_gain(mut self, gain: PGA) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Set the data rate
+    pub fn with_sample_rate(mut self, rate: SampleRates) -> Self {
+        self.rate = rate;
+        self
+    }
+}
+
+impl QwiicADC {
+    /// Read a probe output in volts, averaged and calibration-corrected
+    fn read_probe(&mut self, input: Mux, gain: PGA, rate: SampleRates, samples: usize) -> Result<f32, AdcError> {
+        let volts = self.read_averaged_with(input, gain, rate, samples.max(1))?.volts;
+        Ok(self.config.calibration.apply(input, gain, volts))
+    }
+
+    /// Read the solution temperature of a pH probe in degrees Celsius
+    fn read_solution_temperature(&mut self, probe: &PhProbe) -> Result<Option<f32>, AdcError> {
+        Ok(match probe.temperature {
+            ProbeTemperature::Fixed(celsius) => Some(celsius),
+            ProbeTemperature::Thermistor { channel, thermistor } => self.read_thermistor(channel, &thermistor)?
+                .map(|t| TemperatureUnit::Celsius.from_kelvin(thermistor.unit.to_kelvin(t))),
+        })
+    }
+
+    /// Calibrate a pH probe in one, two or three buffers
+    ///
+    /// The calibration is stored in the configuration's calibration set.
+    ///
+    /// # Arguments
+    /// * `probe` - Probe settings
+    /// * `buffers` - pH of each buffer at the measured temperature
+    /// * `samples` - Conversions averaged in each buffer
+    /// * `prepare` - Called with each buffer's pH before it is measured, e.g.
+    ///   to prompt for the probe to be rinsed and placed in the buffer
+    ///
+    /// # Returns
+    /// * `Ok(calibration)` - The fitted calibration
+    /// * `Err(AdcError::InvalidCalibration)` if the buffers give no usable fit
+    pub fn calibrate_ph<F>(&mut self, probe: &PhProbe, buffers: &[f32], samples: usize, mut prepare: F) -> Result<PhCalibration, AdcError>
+    where
+        F: FnMut(f32),
+    {
+        let mut points = Vec::with_capacity(buffers.len());
+        for &ph in buffers {
+            prepare(ph);
+            let celsius = self.read_solution_temperature(probe)?
+                .ok_or_else(|| AdcError::InvalidCalibration("solution temperature could not be read".to_string()))?;
+            let volts = self.read_probe(probe.input, probe.gain, probe.rate, samples)?;
+            points.push(BufferPoint { volts, ph, celsius });
+        }
+
+        let calibration = PhCalibration::from_points(&points, probe.nominal_slope)
+            .ok_or_else(|| AdcError::InvalidCalibration("pH buffers give no usable slope".to_string()))?;
+        self.config.calibration.set_ph(probe.input, calibration);
+        Ok(calibration)
+    }
+
+    /// Take a temperature-compensated pH reading
+    ///
+    /// # Returns
+    /// * `Ok(reading)` - The pH reading
+    /// * `Err(AdcError::InvalidCalibration)` if the probe's input has no pH calibration
+    pub fn read_ph(&mut self, probe: &PhProbe) -> Result<PhReading, AdcError> {
+        let calibration = self.config.calibration.ph(probe.input)
+            .ok_or_else(|| AdcError::InvalidCalibration(format!("no pH calibration for {:?}", probe.input)))?;
+        let celsius = self.read_solution_temperature(probe)?;
+        let volts = self.read_probe(probe.input, probe.gain, probe.rate, 1)?;
+        Ok(PhReading {
+            ph: calibration.ph(volts, celsius.unwrap_or(25.0)),
+            volts,
+            celsius,
+        })
+    }
+
+    /// Calibrate an ORP probe in a standard solution
+    ///
+    /// The offset is stored in the configuration's calibration set.
+    ///
+    /// # Arguments
+    /// * `probe` - Probe settings, with the probe in the standard
+    /// * `standard_mv` - Potential of the standard in millivolts
+    /// * `samples` - Conversions averaged
+    ///
+    /// # Returns
+    /// * `Ok(offset)` - Offset in volts subtracted from readings
+    pub fn calibrate_orp(&mut self, probe: &OrpProbe, standard_mv: f32, samples: usize) -> Result<f32, AdcError> {
+        let volts = self.read_probe(probe.input, probe.gain, probe.rate, samples)?;
+        let offset = volts - standard_mv / 1000.0;
+        self.config.calibration.set_orp_offset(probe.input, offset);
+        Ok(offset)
+    }
+
+    /// Read the potential of an ORP probe in millivolts
+    ///
+    /// Uncalibrated probes are read without an offset.
+    pub fn read_orp(&mut self, probe: &OrpProbe) -> Result<f32, AdcError> {
+        let volts = self.read_probe(probe.input, probe.gain, probe.rate, 1)?;
+        let offset = self.config.calibration.orp_offset(probe.input).unwrap_or(0.0);
+        Ok((volts - offset) * 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CalibrationSet;
+
+    /// Amplified probe: 2.5 V at pH 7, -0.17 V/pH at 25 °C
+    fn output(ph: f32, celsius: f32) -> f32 {
+        2.5 - 0.17 * (celsius + 273.15) / 298.15 * (ph - 7.0)
+    }
+
+    fn point(ph: f32, celsius: f32) -> BufferPoint {
+        BufferPoint { volts: output(ph, celsius), ph, celsius }
+    }
+
+    #[test]
+    fn test_single_point() {
+        let calibration = PhCalibration::from_points(&[BufferPoint { volts: 0.012, ph: 7.0, celsius: 25.0 }], NERNST_SLOPE).unwrap();
+        assert!((calibration.offset - 0.012).abs() < 1e-6);
+        assert_eq!(calibration.slope, NERNST_SLOPE);
+        assert!((calibration.ph(0.012 + 2.0 * NERNST_SLOPE, 25.0) - 9.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_two_and_three_point() {
+        let calibration = PhCalibration::from_points(&[point(7.0, 25.0), point(4.0, 25.0)], NERNST_SLOPE).unwrap();
+        assert!((calibration.offset - 2.5).abs() < 1e-4);
+        assert!((calibration.slope_mv() + 170.0).abs() < 0.01);
+        assert!((calibration.efficiency(-0.17) - 100.0).abs() < 0.01);
+
+        // Buffers measured at different temperatures
+        let points = [point(4.0, 20.0), point(7.0, 22.0), point(10.0, 24.0)];
+        let calibration = PhCalibration::from_points(&points, NERNST_SLOPE).unwrap();
+        assert!((calibration.offset - 2.5).abs() < 1e-4);
+        assert!((calibration.slope + 0.17).abs() < 1e-4);
+
+        assert!(PhCalibration::from_points(&[], NERNST_SLOPE).is_none());
+        assert!(PhCalibration::from_points(&[point(7.0, 25.0), point(7.0, 25.0)], NERNST_SLOPE).is_none());
+    }
+
+    #[test]
+    fn test_temperature_compensation() {
+        let calibration = PhCalibration { offset: 2.5, slope: -0.17 };
+        for &celsius in &[5.0, 25.0, 50.0] {
+            assert!((calibration.ph(output(4.0, celsius), celsius) - 4.0).abs() < 1e-3);
+            assert!((calibration.ph(output(9.5, celsius), celsius) - 9.5).abs() < 1e-3);
+        }
+        // Without compensation a hot solution reads too far from neutral
+        assert!(calibration.ph(output(10.0, 50.0), 25.0) > 10.2);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let mut set = CalibrationSet::new();
+        set.set_ph(Mux::Single0, PhCalibration { offset: 2.5, slope: -0.17 });
+        set.set_orp_offset(Mux::Single1, 0.012);
+        assert!(!set.is_empty());
+
+        let loaded = CalibrationSet::from_text(&set.to_text()).unwrap();
+        assert_eq!(loaded, set);
+        assert_eq!(loaded.ph(Mux::Single0), Some(PhCalibration { offset: 2.5, slope: -0.17 }));
+        assert_eq!(loaded.orp_offset(Mux::Single1), Some(0.012));
+        assert_eq!(loaded.ph(Mux::Single1), None);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_ph_probe_hardware() {
+        // Bare electrode with an ideal slope between A0 and a mid-supply
+        // reference on A1, in a solution at 25 °C
+        let calibration = PhCalibration { offset: 0.0, slope: NERNST_SLOPE };
+        let mut calibrations = CalibrationSet::new();
+        calibrations.set_ph(Mux::DiffP0N1, calibration);
+        let config = crate::QwiicADCConfig::new("ADS1115".to_string()).with_calibration(calibrations);
+        let mut adc = crate::test_device(config);
+
+        let probe = PhProbe::new(Mux::DiffP0N1).with_gain(PGA::Four);
+        let reading = adc.read_ph(&probe).expect("Failed to read");
+        assert_eq!(reading.celsius, Some(25.0));
+        assert!(reading.volts.abs() <= 7.0 * -NERNST_SLOPE, "{} V is beyond pH 0 to 14", reading.volts);
+        assert!((reading.ph - calibration.ph(reading.volts, 25.0)).abs() < 1e-4);
+        assert!((0.0..=14.0).contains(&reading.ph), "pH {} at {} V", reading.ph, reading.volts);
+        assert_eq!(adc.clipping().count(Mux::DiffP0N1), 0);
+
+        let uncalibrated = PhProbe::new(Mux::DiffP2N3);
+        assert!(matches!(adc.read_ph(&uncalibrated), Err(AdcError::InvalidCalibration(_))));
+    }
+}
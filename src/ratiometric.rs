@@ -0,0 +1,164 @@
+//! Ratiometric measurement
+//!
+//! Potentiometers and bridges powered from the ADC's supply produce a voltage
+//! proportional to that supply. `QwiicADC::read_ratiometric` measures a
+//! reference input (the supply, usually through a divider) immediately before
+//! and after the signal and returns the signal as a fraction of the supply, so
+//! supply variation cancels.
+
+use crate::{AdcError, Mux, QwiicADC, SampleRates, ScanEntry, ScanList, PGA};
+
+/// Reference input and settings for ratiometric reads
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ratiometric {
+    /// Input connected to the supply (or a divided-down copy of it)
+    pub reference: Mux,
+    /// Supply voltage divided by the voltage at the reference input (default: 1)
+    pub divider: f32,
+    /// PGA gain setting used for both conversions (default: `PGA::Two`)
+    pub gain: PGA,
+    /// Data rate used for both conversions (default: 1600 SPS)
+    pub rate: SampleRates,
+}
+
+impl Ratiometric {
+    /// Create settings for a reference input connected directly to the supply
+    pub fn new(reference: Mux) -> Ratiometric {
+        Ratiometric {
+            reference,
+            divider: 1.0,
+            gain: PGA::Two,
+            rate: SampleRates::S1600Hz,
+        }
+    }
+
+    /// Set the divider ratio between the supply and the reference input
+    ///
+    /// # Arguments
+    /// * `ratio` - Supply voltage over reference input voltage, e.g. 2.0 for two equal resistors
+    pub fn with_divider(mut self, ratio: f32) -> Self {
+        self.divider = ratio;
+        self
+    }
+
+    /// Set the PGA gain setting; both inputs must be within its range
+    pub fn with_gain(mut self, gain: PGA) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Set the data rate
+    pub fn with_sample_rate(mut self, rate: SampleRates) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Scan list reading the reference, the signal and the reference again
+    pub fn scan_list(&self, input: Mux) -> ScanList {
+        let entry = |input| ScanEntry::new(input).with_gain(self.gain).with_sample_rate(self.rate);
+        ScanList::new()
+            .with_entry(entry(self.reference))
+            .with_entry(entry(input))
+            .with_entry(entry(self.reference))
+    }
+}
+
+/// Result of a ratiometric read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RatiometricReading {
+    /// Signal as a fraction of the supply
+    pub ratio: f32,
+    /// Signal in volts
+    pub signal_volts: f32,
+    /// Supply in volts, the mean of the readings before and after the signal
+    pub supply_volts: f32,
+    /// Change of the supply between the readings before and after the signal, in volts
+    pub supply_drift: f32,
+}
+
+impl RatiometricReading {
+    /// Combine the signal with the reference readings taken around it
+    ///
+    /// The ratio is infinite or NaN if the supply reads zero.
+    fn from_volts(signal: f32, before: f32, after: f32, divider: f32) -> RatiometricReading {
+        let supply = (before + after) / 2.0 * divider;
+        RatiometricReading {
+            ratio: signal / supply,
+            signal_volts: signal,
+            supply_volts: supply,
+            supply_drift: (after - before) * divider,
+        }
+    }
+}
+
+impl QwiicADC {
+    /// Read an input as a fraction of the supply
+    ///
+    /// The reference, the signal and the reference again are read as one
+    /// continuous-mode scan, so the conversions follow each other at the data
+    /// rate and the supply is sampled on both sides of the signal. Configured
+    /// calibration is applied to each conversion.
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential signal input
+    /// * `settings` - Reference input, divider, gain and rate
+    pub fn read_ratiometric(&mut self, input: Mux, settings: &Ratiometric) -> Result<RatiometricReading, AdcError> {
+        let record = self.scan(&settings.scan_list(input))?;
+        let volts: Vec<f32> = record.values.iter().map(|value| value.volts).collect();
+        Ok(RatiometricReading::from_volts(volts[1], volts[0], volts[2], settings.divider))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ratio_cancels_supply() {
+        // A pot at 25% reads the same fraction whatever the supply
+        for supply in [3.0f32, 3.3, 3.6] {
+            let reading = RatiometricReading::from_volts(0.25 * supply, supply / 2.0, supply / 2.0, 2.0);
+            assert!((reading.ratio - 0.25).abs() < 1e-6);
+            assert!((reading.supply_volts - supply).abs() < 1e-6);
+            assert_eq!(reading.supply_drift, 0.0);
+        }
+    }
+
+    #[test]
+    fn test_reference_readings_are_averaged() {
+        let reading = RatiometricReading::from_volts(1.65, 1.6, 1.7, 2.0);
+        assert!((reading.supply_volts - 3.3).abs() < 1e-6);
+        assert!((reading.supply_drift - 0.2).abs() < 1e-6);
+        assert!((reading.ratio - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_settings() {
+        let settings = Ratiometric::new(Mux::Single3)
+            .with_divider(2.0)
+            .with_gain(PGA::One)
+            .with_sample_rate(SampleRates::S490Hz);
+        assert_eq!(settings.reference, Mux::Single3);
+        assert_eq!(settings.divider, 2.0);
+        assert_eq!(settings.gain, PGA::One);
+        assert_eq!(settings.rate, SampleRates::S490Hz);
+
+        let inputs: Vec<Mux> = settings.scan_list(Mux::Single0).entries().iter().map(|entry| entry.input).collect();
+        assert_eq!(inputs, [Mux::Single3, Mux::Single0, Mux::Single3]);
+        assert!(settings.scan_list(Mux::Single0).entries().iter().all(|entry| entry.gain == PGA::One));
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_ratiometric_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+
+        // A3 reads the 3.3 V supply through a 2:1 divider; A0 is a potentiometer across it
+        let settings = Ratiometric::new(Mux::Single3).with_divider(2.0);
+        let reading = adc.read_ratiometric(Mux::Single0, &settings).expect("Failed to read");
+        assert!((0.0..=1.0).contains(&reading.ratio), "Ratio {}", reading.ratio);
+        assert!((reading.supply_volts - 3.3).abs() < 0.3, "Supply reads {} V", reading.supply_volts);
+        assert!(reading.supply_drift.abs() < 0.05, "Supply moved {} V during the reading", reading.supply_drift);
+        assert!((reading.ratio - reading.signal_volts / reading.supply_volts).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,233 @@
+//! Checked readings
+//!
+//! A full-scale conversion code only says the input is at or beyond the end of
+//! the range, so converting it to volts gives a misleading value. [`Reading`]
+//! carries the conversion with its saturation state, and warns when the PGA
+//! range reaches past the supply: the inputs cannot exceed VDD + 0.3 V, so the
+//! top of such a range is unreachable and a reading there indicates a fault.
+//! Saturated conversions are counted per input by every reader whose input is
+//! known: checked reads, scans, streams, background acquisition, averaging,
+//! auto-ranging, statistics, the sensor helpers and
+//! `get_single_ended`/`get_differential`. Bare `read_input` and
+//! `read_last_conversion` calls are not counted.
+
+use crate::{AdcError, Mux, QwiicADC, SampleRates, PGA};
+
+/// Largest input voltage above the supply the chip tolerates
+const SUPPLY_MARGIN_VOLTS: f32 = 0.3;
+
+/// Saturation state of a conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Saturation {
+    /// The conversion is within range
+    None,
+    /// The conversion is the positive full-scale code
+    Positive,
+    /// The conversion is the negative full-scale code
+    Negative,
+}
+
+impl Saturation {
+    /// Classify signed counts against the positive full-scale count
+    pub(crate) fn from_counts(counts: i16, full_scale_counts: f32) -> Saturation {
+        let counts = counts as f32;
+        if counts >= full_scale_counts - 1.0 {
+            Saturation::Positive
+        } else if counts <= -full_scale_counts {
+            Saturation::Negative
+        } else {
+            Saturation::None
+        }
+    }
+
+    /// Check if the conversion is clipped at either end of the range
+    pub fn is_saturated(self) -> bool {
+        self != Saturation::None
+    }
+}
+
+impl PGA {
+    /// Check if the full-scale range reaches past what the inputs can be driven
+    /// to with a supply voltage
+    pub fn exceeds_supply(self, supply_volts: f32) -> bool {
+        self.full_scale_mv() / 1000.0 > supply_volts + SUPPLY_MARGIN_VOLTS
+    }
+}
+
+/// A conversion with its range checks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reading {
+    /// Input read
+    pub input: Mux,
+    /// PGA gain setting used
+    pub gain: PGA,
+    /// Signed conversion result
+    pub counts: i16,
+    /// Calibration-corrected voltage
+    pub volts: f32,
+    /// Whether the conversion is a full-scale code
+    pub saturation: Saturation,
+    /// Whether the PGA range exceeds the supply voltage plus 0.3 V
+    pub range_exceeds_supply: bool,
+}
+
+impl Reading {
+    /// Check if the conversion is clipped at either end of the range
+    pub fn is_saturated(&self) -> bool {
+        self.saturation.is_saturated()
+    }
+}
+
+/// Saturated conversions per input
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClipCounter {
+    counts: Vec<(Mux, u32)>,
+}
+
+impl ClipCounter {
+    /// Create a counter with no clipping recorded
+    pub fn new() -> ClipCounter {
+        ClipCounter::default()
+    }
+
+    /// Record a saturated conversion of an input
+    pub fn record(&mut self, input: Mux) {
+        match self.counts.iter_mut().find(|(i, _)| *i == input) {
+            Some((_, count)) => *count += 1,
+            None => self.counts.push((input, 1)),
+        }
+    }
+
+    /// Number of saturated conversions of an input
+    pub fn count(&self, input: Mux) -> u32 {
+        self.counts.iter().find(|(i, _)| *i == input).map_or(0, |&(_, count)| count)
+    }
+
+    /// Number of saturated conversions of all inputs
+    pub fn total(&self) -> u32 {
+        self.counts.iter().map(|&(_, count)| count).sum()
+    }
+
+    /// Inputs with saturated conversions and their counts, in order of first clipping
+    pub fn counts(&self) -> &[(Mux, u32)] {
+        &self.counts
+    }
+
+    /// Clear all counts
+    pub fn reset(&mut self) {
+        self.counts.clear();
+    }
+}
+
+impl QwiicADC {
+    /// Check a raw conversion of an input and count it if saturated
+    ///
+    /// # Arguments
+    /// * `input` - Input the conversion was taken from
+    /// * `gain` - PGA gain setting used for the conversion
+    /// * `raw` - Raw conversion code
+    pub fn check_conversion(&mut self, input: Mux, gain: PGA, raw: u16) -> Reading {
+        let counts = self.raw_to_signed(raw);
+        let saturation = self.count_clipping(input, raw);
+        let volts = self.counts_to_volts(counts as f32, gain);
+        Reading {
+            input,
+            gain,
+            counts,
+            volts: self.config.calibration.apply(input, gain, volts),
+            saturation,
+            range_exceeds_supply: gain.exceeds_supply(self.config.supply_volts),
+        }
+    }
+
+    /// Classify a raw conversion of an input and count it if saturated
+    pub(crate) fn count_clipping(&mut self, input: Mux, raw: u16) -> Saturation {
+        let saturation = Saturation::from_counts(self.raw_to_signed(raw), self.full_scale_counts());
+        if saturation.is_saturated() {
+            self.clipping.record(input);
+        }
+        saturation
+    }
+
+    /// Take a single-shot reading with saturation and range checks
+    ///
+    /// # Arguments
+    /// * `input` - Input multiplexer setting
+    /// * `gain` - PGA gain setting
+    /// * `rate` - Sample rate setting
+    pub fn read(&mut self, input: Mux, gain: PGA, rate: SampleRates) -> Result<Reading, AdcError> {
+        let raw = self.read_input(input, gain, rate)?;
+        Ok(self.check_conversion(input, gain, raw))
+    }
+
+    /// Saturated conversions counted per input
+    pub fn clipping(&self) -> &ClipCounter {
+        &self.clipping
+    }
+
+    /// Clear the saturated conversion counts
+    pub fn reset_clipping(&mut self) {
+        self.clipping.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saturation_codes() {
+        // ADS1115
+        assert_eq!(Saturation::from_counts(0x7FFF, 32768.0), Saturation::Positive);
+        assert_eq!(Saturation::from_counts(-0x8000, 32768.0), Saturation::Negative);
+        assert_eq!(Saturation::from_counts(0x7FFE, 32768.0), Saturation::None);
+        assert_eq!(Saturation::from_counts(-0x7FFF, 32768.0), Saturation::None);
+
+        // ADS1015
+        assert_eq!(Saturation::from_counts(2047, 2048.0), Saturation::Positive);
+        assert_eq!(Saturation::from_counts(-2048, 2048.0), Saturation::Negative);
+        assert!(!Saturation::from_counts(2046, 2048.0).is_saturated());
+    }
+
+    #[test]
+    fn test_range_exceeds_supply() {
+        assert!(PGA::TwoThirds.exceeds_supply(3.3), "6.144 V is beyond 3.6 V");
+        assert!(PGA::One.exceeds_supply(3.3), "4.096 V is beyond 3.6 V");
+        assert!(!PGA::Two.exceeds_supply(3.3));
+        assert!(!PGA::One.exceeds_supply(5.0));
+        assert!(!PGA::TwoThirds.exceeds_supply(5.9));
+    }
+
+    #[test]
+    fn test_clip_counter() {
+        let mut counter = ClipCounter::new();
+        counter.record(Mux::Single2);
+        counter.record(Mux::DiffP0N1);
+        counter.record(Mux::Single2);
+
+        assert_eq!(counter.count(Mux::Single2), 2);
+        assert_eq!(counter.count(Mux::DiffP0N1), 1);
+        assert_eq!(counter.count(Mux::Single0), 0);
+        assert_eq!(counter.total(), 3);
+        assert_eq!(counter.counts()[0], (Mux::Single2, 2));
+
+        counter.reset();
+        assert_eq!(counter.total(), 0);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_reading_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default().with_supply_voltage(3.3));
+        adc.reset_clipping();
+
+        let reading = adc.read(Mux::Single0, PGA::Two, SampleRates::S1600Hz).expect("Failed to read");
+        assert_eq!((reading.input, reading.gain), (Mux::Single0, PGA::Two));
+        assert!(!reading.range_exceeds_supply);
+        assert!(reading.volts.abs() <= 2.048 || reading.is_saturated());
+        assert_eq!(adc.clipping().count(Mux::Single0), reading.is_saturated() as u32);
+
+        let reading = adc.read(Mux::Single0, PGA::One, SampleRates::S1600Hz).expect("Failed to read");
+        assert!(reading.range_exceeds_supply, "4.096 V is beyond a 3.3 V supply");
+    }
+}
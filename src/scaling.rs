@@ -0,0 +1,182 @@
+//! Engineering-unit scaling
+//!
+//! A [`Scaling`] converts volts into a physical quantity with a linear,
+//! polynomial or piecewise-linear curve and records the unit of the result.
+//! Scalings attached to a `ScanList` turn each scan value into a
+//! [`Measurement`].
+
+use std::fmt;
+
+/// A value in engineering units
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    /// Scaled value
+    pub value: f32,
+    /// Unit of the value, e.g. "kPa"
+    pub unit: String,
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match f.precision() {
+            Some(precision) => write!(f, "{:.*} {}", precision, self.value, self.unit),
+            None => write!(f, "{} {}", self.value, self.unit),
+        }
+    }
+}
+
+/// Curve mapping volts to engineering units
+#[derive(Debug, Clone, PartialEq)]
+enum Curve {
+    /// `value = gain * volts + offset`
+    Linear { gain: f32, offset: f32 },
+    /// `value = c0 + c1 * volts + c2 * volts^2 + ...`
+    Polynomial(Vec<f32>),
+    /// Interpolated table of (volts, value) points sorted by volts
+    Piecewise(Vec<(f32, f32)>),
+}
+
+/// Conversion from volts to engineering units
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scaling {
+    curve: Curve,
+    unit: String,
+}
+
+impl Scaling {
+    /// Create a linear scaling `value = gain * volts + offset`
+    pub fn linear(gain: f32, offset: f32, unit: &str) -> Scaling {
+        Scaling {
+            curve: Curve::Linear { gain, offset },
+            unit: unit.to_string(),
+        }
+    }
+
+    /// Create a linear scaling through two (volts, value) points
+    ///
+    /// # Returns
+    /// The scaling, or `None` if both points have the same voltage
+    pub fn two_point(low: (f32, f32), high: (f32, f32), unit: &str) -> Option<Scaling> {
+        let span = high.0 - low.0;
+        if span == 0.0 {
+            return None;
+        }
+        let gain = (high.1 - low.1) / span;
+        Some(Scaling::linear(gain, low.1 - gain * low.0, unit))
+    }
+
+    /// Create a polynomial scaling
+    ///
+    /// # Arguments
+    /// * `coefficients` - Coefficients in ascending order of power, starting with the constant term
+    /// * `unit` - Unit of the result
+    pub fn polynomial(coefficients: &[f32], unit: &str) -> Scaling {
+        Scaling {
+            curve: Curve::Polynomial(coefficients.to_vec()),
+            unit: unit.to_string(),
+        }
+    }
+
+    /// Create a piecewise-linear scaling from a lookup table
+    ///
+    /// Values between points are interpolated; values outside the table are
+    /// extrapolated from the first or last segment.
+    ///
+    /// # Arguments
+    /// * `points` - (volts, value) pairs, in any order
+    /// * `unit` - Unit of the result
+    ///
+    /// # Returns
+    /// The scaling, or `None` if there are fewer than two points or two share a voltage
+    pub fn piecewise(points: &[(f32, f32)], unit: &str) -> Option<Scaling> {
+        let mut points = points.to_vec();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if points.len() < 2 || points.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return None;
+        }
+        Some(Scaling {
+            curve: Curve::Piecewise(points),
+            unit: unit.to_string(),
+        })
+    }
+
+    /// Unit of the scaled value
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
+
+    /// Convert volts to engineering units
+    pub fn apply(&self, volts: f32) -> f32 {
+        match &self.curve {
+            Curve::Linear { gain, offset } => gain * volts + offset,
+            Curve::Polynomial(coefficients) => coefficients.iter()
+                .rev()
+                .fold(0.0, |acc, &c| acc * volts + c),
+            Curve::Piecewise(points) => {
+                // Segment containing the value, or the nearest end segment
+                let index = points.iter()
+                    .position(|&(v, _)| v > volts)
+                    .unwrap_or(points.len())
+                    .clamp(1, points.len() - 1);
+                let (v0, y0) = points[index - 1];
+                let (v1, y1) = points[index];
+                y0 + (volts - v0) * (y1 - y0) / (v1 - v0)
+            },
+        }
+    }
+
+    /// Convert volts to a measurement carrying the unit
+    pub fn measure(&self, volts: f32) -> Measurement {
+        Measurement {
+            value: self.apply(volts),
+            unit: self.unit.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_point() {
+        // 0.5 V = 0 kPa, 4.5 V = 100 kPa
+        let scaling = Scaling::two_point((0.5, 0.0), (4.5, 100.0), "kPa").unwrap();
+        assert_eq!(scaling.apply(0.5), 0.0);
+        assert_eq!(scaling.apply(2.5), 50.0);
+        assert_eq!(scaling.apply(4.5), 100.0);
+        assert_eq!(scaling.unit(), "kPa");
+
+        assert_eq!(Scaling::two_point((1.0, 0.0), (1.0, 5.0), "kPa"), None);
+    }
+
+    #[test]
+    fn test_polynomial() {
+        // 1 + 2v + 3v^2
+        let scaling = Scaling::polynomial(&[1.0, 2.0, 3.0], "mm");
+        assert_eq!(scaling.apply(0.0), 1.0);
+        assert_eq!(scaling.apply(2.0), 17.0);
+        assert_eq!(Scaling::polynomial(&[], "mm").apply(3.0), 0.0);
+    }
+
+    #[test]
+    fn test_piecewise() {
+        let scaling = Scaling::piecewise(&[(2.0, 50.0), (0.0, 0.0), (3.0, 100.0)], "%").unwrap();
+        assert_eq!(scaling.apply(1.0), 25.0);
+        assert_eq!(scaling.apply(2.0), 50.0);
+        assert_eq!(scaling.apply(2.5), 75.0);
+        // Extrapolated from the end segments
+        assert_eq!(scaling.apply(-1.0), -25.0);
+        assert_eq!(scaling.apply(4.0), 150.0);
+
+        assert_eq!(Scaling::piecewise(&[(1.0, 0.0)], "%"), None);
+        assert_eq!(Scaling::piecewise(&[(1.0, 0.0), (1.0, 2.0)], "%"), None);
+    }
+
+    #[test]
+    fn test_measurement_display() {
+        let measurement = Scaling::linear(10.0, 0.0, "bar").measure(0.1234);
+        assert_eq!(measurement.unit, "bar");
+        assert_eq!(format!("{:.2}", measurement), "1.23 bar");
+    }
+}
@@ -0,0 +1,326 @@
+//! Multi-channel scanning
+//!
+//! A [`ScanList`] describes an ordered set of inputs, each with its own gain,
+//! data rate and oversampling count. `QwiicADC::scan` runs the list in
+//! continuous mode and only rewrites the configuration register when an entry
+//! differs from the one before it. A [`Scaling`] attached to an input turns its
+//! values into engineering-unit measurements.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{AdcError, Measurement, Mux, QwiicADC, SampleRates, Scaling, PGA};
+
+/// One input of a scan list with its conversion settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanEntry {
+    /// Single-ended or differential input
+    pub input: Mux,
+    /// PGA gain setting (default: `PGA::Two`)
+    pub gain: PGA,
+    /// Data rate (default: 1600 SPS)
+    pub rate: SampleRates,
+    /// Number of conversions averaged into the result (default: 1)
+    pub oversample: u16,
+}
+
+impl ScanEntry {
+    /// Create an entry with the default gain, rate and no oversampling
+    pub fn new(input: Mux) -> ScanEntry {
+        ScanEntry {
+            input,
+            gain: PGA::Two,
+            rate: SampleRates::S1600Hz,
+            oversample: 1,
+        }
+    }
+
+    /// Set the PGA gain
+    pub fn with_gain(mut self, gain: PGA) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Set the data rate
+    pub fn with_sample_rate(mut self, rate: SampleRates) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Set the number of conversions averaged into the result
+    pub fn with_oversampling(mut self, count: u16) -> Self {
+        self.oversample = count.max(1);
+        self
+    }
+
+    /// Check if switching from `other` to this entry needs a configuration write
+    fn needs_write(&self, other: Option<&ScanEntry>) -> bool {
+        match other {
+            Some(other) => (self.input, self.gain, self.rate) != (other.input, other.gain, other.rate),
+            None => true,
+        }
+    }
+}
+
+/// Ordered set of inputs read by `QwiicADC::scan`
+#[derive(Debug, Clone, Default)]
+pub struct ScanList {
+    entries: Vec<ScanEntry>,
+    scalings: Vec<(Mux, Scaling)>,
+}
+
+impl ScanList {
+    /// Create an empty scan list
+    pub fn new() -> ScanList {
+        ScanList::default()
+    }
+
+    /// Create a scan list of single-ended channels with default settings
+    ///
+    /// # Arguments
+    /// * `channels` - Channel numbers (must be 0-3)
+    pub fn single_ended(channels: &[u8]) -> Result<ScanList, AdcError> {
+        let mut list = ScanList::new();
+        for &channel in channels {
+            list.push(ScanEntry::new(Mux::single(channel)?));
+        }
+        Ok(list)
+    }
+
+    /// Append an entry to the list
+    pub fn with_entry(mut self, entry: ScanEntry) -> Self {
+        self.push(entry);
+        self
+    }
+
+    /// Append an entry to the list
+    pub fn push(&mut self, entry: ScanEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Scale the values of an input into engineering units
+    pub fn with_scaling(mut self, input: Mux, scaling: Scaling) -> Self {
+        self.set_scaling(input, scaling);
+        self
+    }
+
+    /// Scale the values of an input into engineering units, replacing any previous scaling
+    pub fn set_scaling(&mut self, input: Mux, scaling: Scaling) {
+        self.scalings.retain(|(i, _)| *i != input);
+        self.scalings.push((input, scaling));
+    }
+
+    /// Get the scaling of an input
+    pub fn scaling(&self, input: Mux) -> Option<&Scaling> {
+        self.scalings.iter().find(|(i, _)| *i == input).map(|(_, scaling)| scaling)
+    }
+
+    /// Entries in scan order
+    pub fn entries(&self) -> &[ScanEntry] {
+        &self.entries
+    }
+
+    /// Number of entries in the list
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the list has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of configuration register writes needed to run the list once
+    pub fn config_writes(&self) -> usize {
+        let mut previous = None;
+        let mut writes = 0;
+        for entry in &self.entries {
+            if entry.needs_write(previous) {
+                writes += 1;
+            }
+            previous = Some(entry);
+        }
+        writes
+    }
+}
+
+/// Result of one scan list entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanValue {
+    /// Input the value was read from
+    pub input: Mux,
+    /// PGA gain setting used for the conversions
+    pub gain: PGA,
+    /// Mean signed conversion result in counts
+    pub counts: f32,
+    /// Mean conversion result in volts, corrected by the configured calibration
+    pub volts: f32,
+    /// Value in engineering units, if the input has a scaling
+    pub measurement: Option<Measurement>,
+    /// Time at which the last conversion of the entry was read
+    pub timestamp: Instant,
+}
+
+/// Results of running a scan list once
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanRecord {
+    /// Time at which the scan started
+    pub timestamp: Instant,
+    /// One value per scan list entry, in scan order
+    pub values: Vec<ScanValue>,
+}
+
+impl ScanRecord {
+    /// Get the first value read from an input
+    pub fn get(&self, input: Mux) -> Option<&ScanValue> {
+        self.values.iter().find(|value| value.input == input)
+    }
+
+    /// Get the measurement of the first value read from a scaled input
+    pub fn measurement(&self, input: Mux) -> Option<&Measurement> {
+        self.get(input)?.measurement.as_ref()
+    }
+}
+
+impl QwiicADC {
+    /// Read every entry of a scan list once
+    ///
+    /// The list runs in continuous mode; the configuration is only rewritten when
+    /// an entry's input, gain or rate differs from the previous entry, and the
+    /// conversion in flight at each rewrite is discarded. Entries that only change
+    /// the input use `switch_channel_continuous`.
+    ///
+    /// # Arguments
+    /// * `list` - Inputs and settings to read, in order
+    ///
+    /// # Returns
+    /// One record with a value per entry
+    pub fn scan(&mut self, list: &ScanList) -> Result<ScanRecord, AdcError> {
+        let timestamp = Instant::now();
+        let mut values = Vec::with_capacity(list.len());
+        let mut previous: Option<&ScanEntry> = None;
+
+        for entry in list.entries() {
+            let period = Duration::from_secs_f32(1.0 / self.data_rate_hz(entry.rate));
+            match previous {
+                Some(prev) if !entry.needs_write(Some(prev)) => thread::sleep(period),
+                Some(prev) if (prev.gain, prev.rate) == (entry.gain, entry.rate) => {
+                    self.switch_channel_continuous(entry.input)?;
+                },
+                _ => {
+                    self.start_continuous_input(entry.input, entry.gain, entry.rate)?;
+                    thread::sleep(self.settling_time(entry.rate as u16));
+                },
+            }
+
+            let mut total = 0.0;
+            for i in 0..entry.oversample {
+                if i > 0 {
+                    thread::sleep(period);
+                }
+                let raw = self.read_last_conversion()?;
+                total += self.check_conversion(entry.input, entry.gain, raw).counts as f32;
+            }
+
+            let counts = total / entry.oversample as f32;
+            let volts = self.config.calibration.apply(entry.input, entry.gain, self.counts_to_volts(counts, entry.gain));
+            values.push(ScanValue {
+                input: entry.input,
+                gain: entry.gain,
+                counts,
+                volts,
+                measurement: list.scaling(entry.input).map(|scaling| scaling.measure(volts)),
+                timestamp: Instant::now(),
+            });
+            previous = Some(entry);
+        }
+
+        if !list.is_empty() {
+            self.stop_continuous()?;
+        }
+        Ok(ScanRecord { timestamp, values })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_entry_defaults() {
+        let entry = ScanEntry::new(Mux::DiffP0N1);
+        assert_eq!(entry.gain, PGA::Two);
+        assert_eq!(entry.rate, SampleRates::S1600Hz);
+        assert_eq!(entry.oversample, 1);
+
+        let entry = entry.with_oversampling(0);
+        assert_eq!(entry.oversample, 1, "Oversampling count should be at least 1");
+    }
+
+    #[test]
+    fn test_single_ended_list() {
+        let list = ScanList::single_ended(&[0, 1, 2, 3]).unwrap();
+        let inputs: Vec<Mux> = list.entries().iter().map(|entry| entry.input).collect();
+        assert_eq!(inputs, vec![Mux::Single0, Mux::Single1, Mux::Single2, Mux::Single3]);
+
+        assert!(ScanList::single_ended(&[0, 4]).is_err());
+    }
+
+    #[test]
+    fn test_config_writes() {
+        assert_eq!(ScanList::new().config_writes(), 0);
+
+        // Every input change needs a write
+        let list = ScanList::single_ended(&[0, 1, 2, 3]).unwrap();
+        assert_eq!(list.config_writes(), 4);
+
+        // Repeating an entry with the same settings reuses the configuration
+        let list = ScanList::new()
+            .with_entry(ScanEntry::new(Mux::Single0))
+            .with_entry(ScanEntry::new(Mux::Single0).with_oversampling(8))
+            .with_entry(ScanEntry::new(Mux::Single0).with_gain(PGA::Four))
+            .with_entry(ScanEntry::new(Mux::Single0).with_gain(PGA::Four))
+            .with_entry(ScanEntry::new(Mux::Single1).with_gain(PGA::Four));
+        assert_eq!(list.config_writes(), 3);
+    }
+
+    #[test]
+    fn test_scalings() {
+        let mut list = ScanList::single_ended(&[0, 1]).unwrap()
+            .with_scaling(Mux::Single0, Scaling::linear(2.0, 0.0, "kPa"));
+        assert_eq!(list.scaling(Mux::Single0).unwrap().unit(), "kPa");
+        assert_eq!(list.scaling(Mux::Single1), None);
+
+        list.set_scaling(Mux::Single0, Scaling::linear(1.0, 0.0, "mm"));
+        assert_eq!(list.scaling(Mux::Single0).unwrap().unit(), "mm");
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_scan_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+
+        let list = ScanList::new()
+            .with_entry(ScanEntry::new(Mux::Single0).with_gain(PGA::One))
+            .with_entry(ScanEntry::new(Mux::Single1).with_oversampling(4))
+            .with_entry(ScanEntry::new(Mux::DiffP2N3).with_sample_rate(SampleRates::S490Hz));
+
+        let record = adc.scan(&list).expect("Failed to scan");
+        assert_eq!(record.values.len(), 3);
+        for (value, entry) in record.values.iter().zip(list.entries()) {
+            assert_eq!((value.input, value.gain), (entry.input, entry.gain), "Values follow the list");
+            let full_scale = entry.gain.full_scale_mv() / 1000.0;
+            assert!(value.volts.abs() <= full_scale, "{:?} read {} V beyond ±{} V", value.input, value.volts, full_scale);
+            assert!(value.measurement.is_none(), "No scaling configured");
+            assert!(value.timestamp >= record.timestamp);
+        }
+
+        let list = ScanList::single_ended(&[0]).unwrap()
+            .with_scaling(Mux::Single0, Scaling::two_point((0.5, 0.0), (4.5, 100.0), "kPa").unwrap());
+        let record = adc.scan(&list).expect("Failed to scan");
+        let pressure = record.measurement(Mux::Single0).expect("Scaling configured");
+        assert_eq!(pressure.unit, "kPa");
+        let volts = record.get(Mux::Single0).unwrap().volts;
+        assert!((pressure.value - (volts - 0.5) * 25.0).abs() < 1e-3);
+    }
+}
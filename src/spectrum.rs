@@ -0,0 +1,427 @@
+//! Spectrum analysis
+//!
+//! [`Spectrum::analyze`] turns a block of readings taken at a known data rate
+//! into a single-sided amplitude spectrum in volts, with the dominant frequency
+//! and an estimate of total harmonic distortion. Blocks whose length is a power
+//! of two use a radix-2 FFT; other lengths fall back to a direct DFT.
+
+use std::f64::consts::PI;
+
+use crate::{AdcError, Mux, QwiicADC, Sample, SampleRates, PGA};
+
+/// Highest harmonic included in the THD estimate
+const MAX_HARMONIC: usize = 10;
+
+/// Number of times a block is captured before missed conversions are reported
+const CAPTURE_ATTEMPTS: usize = 3;
+
+/// Window applied to a block before the transform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowFunction {
+    /// No window; best resolution, most leakage
+    Rectangular,
+    /// Hann window; good general-purpose choice
+    Hann,
+    /// Hamming window; lower first sidelobe than Hann
+    Hamming,
+    /// Blackman window; lowest leakage, widest peaks
+    Blackman,
+}
+
+impl WindowFunction {
+    /// Window coefficient for sample `i` of `n`
+    fn coefficient(self, i: usize, n: usize) -> f64 {
+        if n < 2 {
+            return 1.0;
+        }
+        let x = 2.0 * PI * i as f64 / (n - 1) as f64;
+        match self {
+            WindowFunction::Rectangular => 1.0,
+            WindowFunction::Hann => 0.5 - 0.5 * x.cos(),
+            WindowFunction::Hamming => 0.54 - 0.46 * x.cos(),
+            WindowFunction::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+        }
+    }
+
+    /// Half width of the main lobe in bins, used to find harmonic peaks
+    fn lobe_bins(self) -> usize {
+        match self {
+            WindowFunction::Rectangular => 1,
+            WindowFunction::Hann | WindowFunction::Hamming => 2,
+            WindowFunction::Blackman => 3,
+        }
+    }
+}
+
+/// One frequency bin of a spectrum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectrumBin {
+    /// Centre frequency in hertz
+    pub frequency: f32,
+    /// Peak amplitude in volts (the signed mean for the DC bin)
+    pub magnitude: f32,
+}
+
+/// Single-sided amplitude spectrum of a block of readings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    /// Bins from DC up to half the sample rate
+    pub bins: Vec<SpectrumBin>,
+    /// Spacing between bins in hertz
+    pub resolution: f32,
+    /// Frequency of the largest non-DC component, interpolated between bins
+    pub dominant_frequency: f32,
+    /// Amplitude of the largest non-DC component in volts
+    pub dominant_magnitude: f32,
+    /// Total harmonic distortion as a ratio of harmonic to fundamental amplitude,
+    /// or `None` if no harmonic lies below half the sample rate
+    pub thd: Option<f32>,
+}
+
+impl Spectrum {
+    /// Analyse a block of readings
+    ///
+    /// The mean is removed before windowing and reported as the DC bin.
+    ///
+    /// # Arguments
+    /// * `volts` - Readings in volts, equally spaced in time
+    /// * `sample_rate_hz` - Rate at which the readings were taken
+    /// * `window` - Window applied before the transform
+    pub fn analyze(volts: &[f32], sample_rate_hz: f32, window: WindowFunction) -> Spectrum {
+        let n = volts.len();
+        let resolution = if n > 0 { sample_rate_hz / n as f32 } else { 0.0 };
+        if n < 2 {
+            let mean = volts.first().copied().unwrap_or(0.0);
+            return Spectrum {
+                bins: vec![SpectrumBin { frequency: 0.0, magnitude: mean }],
+                resolution,
+                dominant_frequency: 0.0,
+                dominant_magnitude: 0.0,
+                thd: None,
+            };
+        }
+
+        let mean = volts.iter().map(|&v| v as f64).sum::<f64>() / n as f64;
+        let coefficients: Vec<f64> = (0..n).map(|i| window.coefficient(i, n)).collect();
+        let coherent_gain: f64 = coefficients.iter().sum();
+        let windowed: Vec<(f64, f64)> = volts.iter()
+            .zip(&coefficients)
+            .map(|(&v, &w)| ((v as f64 - mean) * w, 0.0))
+            .collect();
+
+        let transform = if n.is_power_of_two() { fft(windowed) } else { dft(&windowed) };
+        let bins: Vec<SpectrumBin> = transform.iter()
+            .take(n / 2 + 1)
+            .enumerate()
+            .map(|(k, &(re, im))| SpectrumBin {
+                frequency: k as f32 * resolution,
+                magnitude: if k == 0 {
+                    mean as f32
+                } else {
+                    // The Nyquist bin of an even length has no mirror image to fold in
+                    let sides = if 2 * k == n { 1.0 } else { 2.0 };
+                    (sides * (re * re + im * im).sqrt() / coherent_gain) as f32
+                },
+            })
+            .collect();
+
+        let peak = (1..bins.len())
+            .max_by(|&a, &b| bins[a].magnitude.total_cmp(&bins[b].magnitude))
+            .unwrap_or(0);
+        let dominant_frequency = (peak as f32 + interpolate_peak(&bins, peak)) * resolution;
+        let thd = harmonic_distortion(&bins, peak, dominant_frequency / resolution, window.lobe_bins());
+
+        Spectrum {
+            dominant_magnitude: bins[peak].magnitude,
+            bins,
+            resolution,
+            dominant_frequency,
+            thd,
+        }
+    }
+
+    /// Analyse a block of samples from a stream or acquisition
+    ///
+    /// # Arguments
+    /// * `samples` - Consecutive samples of one input, without missed
+    ///   conversions (see `missed_conversions`)
+    /// * `sample_rate_hz` - Data rate the samples were taken at
+    /// * `window` - Window applied before the transform
+    pub fn from_samples(samples: &[Sample], sample_rate_hz: f32, window: WindowFunction) -> Spectrum {
+        let volts: Vec<f32> = samples.iter().map(|sample| sample.volts).collect();
+        Spectrum::analyze(&volts, sample_rate_hz, window)
+    }
+
+    /// Number of conversions missing between consecutive samples of a block,
+    /// found from gaps in their sequence numbers
+    pub fn missed_conversions(samples: &[Sample]) -> u64 {
+        samples.windows(2)
+            .map(|pair| pair[1].seq.saturating_sub(pair[0].seq + 1))
+            .sum()
+    }
+}
+
+impl QwiicADC {
+    /// Capture a block in continuous mode and analyse its spectrum
+    ///
+    /// A block with missed conversions is not evenly spaced and is captured
+    /// again, up to three times.
+    ///
+    /// # Arguments
+    /// * `input` - Single-ended or differential input
+    /// * `gain` - PGA gain setting
+    /// * `rate` - Data rate, which sets the analysed bandwidth
+    /// * `n` - Number of samples; a power of two is fastest
+    /// * `window` - Window applied before the transform
+    ///
+    /// # Returns
+    /// * `Ok(spectrum)` - Spectrum of an evenly spaced block
+    /// * `Err(AdcError::MissedConversions)` if every attempt missed conversions
+    pub fn analyze_spectrum(&mut self, input: Mux, gain: PGA, rate: SampleRates, n: usize, window: WindowFunction) -> Result<Spectrum, AdcError> {
+        let mut missed = 0;
+        for _ in 0..CAPTURE_ATTEMPTS {
+            let samples = self.stream_input(input, gain, rate)?
+                .take(n)
+                .collect::<Result<Vec<_>, _>>()?;
+            missed = Spectrum::missed_conversions(&samples);
+            if missed == 0 {
+                return Ok(Spectrum::from_samples(&samples, self.data_rate_hz(rate), window));
+            }
+        }
+        Err(AdcError::MissedConversions(missed))
+    }
+}
+
+/// Offset of the true peak from bin `k`, from a parabola through its neighbours
+fn interpolate_peak(bins: &[SpectrumBin], k: usize) -> f32 {
+    if k == 0 || k + 1 >= bins.len() {
+        return 0.0;
+    }
+    let (left, centre, right) = (bins[k - 1].magnitude, bins[k].magnitude, bins[k + 1].magnitude);
+    let denominator = left - 2.0 * centre + right;
+    if denominator == 0.0 {
+        0.0
+    } else {
+        (0.5 * (left - right) / denominator).clamp(-0.5, 0.5)
+    }
+}
+
+/// Ratio of the combined harmonic amplitude to the fundamental
+///
+/// Each harmonic's amplitude is the largest bin within the window's main lobe
+/// around its expected position, excluding the fundamental's own lobe.
+fn harmonic_distortion(bins: &[SpectrumBin], fundamental: usize, fundamental_bin: f32, lobe: usize) -> Option<f32> {
+    let fundamental_magnitude = bins.get(fundamental)?.magnitude;
+    if fundamental == 0 || fundamental_magnitude == 0.0 {
+        return None;
+    }
+
+    let mut sum_squares = 0.0;
+    let mut harmonics = 0;
+    for h in 2..=MAX_HARMONIC {
+        let centre = (fundamental_bin * h as f32).round() as usize;
+        if centre >= bins.len() {
+            break;
+        }
+        let start = centre.saturating_sub(lobe).max(fundamental + lobe + 1);
+        let end = (centre + lobe).min(bins.len() - 1);
+        if start > end {
+            continue;
+        }
+        let magnitude = bins[start..=end].iter().map(|bin| bin.magnitude).fold(0.0, f32::max);
+        sum_squares += magnitude * magnitude;
+        harmonics += 1;
+    }
+
+    if harmonics == 0 {
+        None
+    } else {
+        Some(sum_squares.sqrt() / fundamental_magnitude)
+    }
+}
+
+/// In-place iterative radix-2 FFT; the length must be a power of two
+fn fft(mut data: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    let n = data.len();
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (re, im) = data[start + k + len / 2];
+                let twiddled = (re * cos - im * sin, re * sin + im * cos);
+                let even = data[start + k];
+                data[start + k] = (even.0 + twiddled.0, even.1 + twiddled.1);
+                data[start + k + len / 2] = (even.0 - twiddled.0, even.1 - twiddled.1);
+            }
+        }
+        len <<= 1;
+    }
+    data
+}
+
+/// Direct discrete Fourier transform for arbitrary lengths
+fn dft(data: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let n = data.len();
+    (0..n)
+        .map(|k| {
+            data.iter().enumerate().fold((0.0, 0.0), |(sum_re, sum_im), (t, &(re, im))| {
+                let angle = -2.0 * PI * ((k * t) % n) as f64 / n as f64;
+                let (sin, cos) = angle.sin_cos();
+                (sum_re + re * cos - im * sin, sum_im + re * sin + im * cos)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sum of sines given as (frequency, amplitude) pairs, plus an offset
+    fn signal(components: &[(f32, f32)], offset: f32, sample_rate_hz: f32, n: usize) -> Vec<f32> {
+        (0..n)
+            .map(|i| {
+                let t = i as f32 / sample_rate_hz;
+                offset + components.iter()
+                    .map(|&(f, a)| a * (2.0 * std::f32::consts::PI * f * t).sin())
+                    .sum::<f32>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fft_matches_dft() {
+        let data: Vec<(f64, f64)> = (0..16).map(|i| ((i as f64 * 0.7).sin() + 0.3, 0.0)).collect();
+        let fast = fft(data.clone());
+        let direct = dft(&data);
+        for (a, b) in fast.iter().zip(&direct) {
+            assert!((a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_bin_centred_sine() {
+        // 50 Hz falls exactly on a bin: 1024 samples at 1024 SPS
+        let volts = signal(&[(50.0, 0.8)], -1.2, 1024.0, 1024);
+        let spectrum = Spectrum::analyze(&volts, 1024.0, WindowFunction::Rectangular);
+
+        assert_eq!(spectrum.bins.len(), 513);
+        assert_eq!(spectrum.resolution, 1.0);
+        assert!((spectrum.dominant_frequency - 50.0).abs() < 1e-3);
+        assert!((spectrum.dominant_magnitude - 0.8).abs() < 1e-3);
+        assert!((spectrum.bins[0].magnitude + 1.2).abs() < 1e-4, "DC bin should hold the signed mean");
+    }
+
+    #[test]
+    fn test_nyquist_tone() {
+        // A tone at fs/2 alternates sign every sample and lands in the last bin
+        let volts: Vec<f32> = (0..64).map(|i| if i % 2 == 0 { 0.5 } else { -0.5 }).collect();
+        let spectrum = Spectrum::analyze(&volts, 128.0, WindowFunction::Rectangular);
+        assert_eq!(spectrum.bins.len(), 33);
+        assert_eq!(spectrum.dominant_frequency, 64.0);
+        assert!((spectrum.dominant_magnitude - 0.5).abs() < 1e-4, "Magnitude {}", spectrum.dominant_magnitude);
+
+        // Odd lengths have no Nyquist bin, so every bin is folded
+        let volts = signal(&[(20.0, 0.3)], 0.0, 100.0, 25);
+        let spectrum = Spectrum::analyze(&volts, 100.0, WindowFunction::Rectangular);
+        assert_eq!(spectrum.bins.len(), 13);
+        assert!((spectrum.dominant_magnitude - 0.3).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_windowed_sine_between_bins() {
+        let volts = signal(&[(60.3, 1.0)], 0.0, 920.0, 512);
+        for window in [WindowFunction::Hann, WindowFunction::Hamming, WindowFunction::Blackman] {
+            let spectrum = Spectrum::analyze(&volts, 920.0, window);
+            assert!((spectrum.dominant_frequency - 60.3).abs() < spectrum.resolution / 2.0,
+                "{:?}: {} Hz", window, spectrum.dominant_frequency);
+            assert!(spectrum.dominant_magnitude > 0.8 && spectrum.dominant_magnitude < 1.05,
+                "{:?}: {} V", window, spectrum.dominant_magnitude);
+        }
+    }
+
+    #[test]
+    fn test_non_power_of_two_length() {
+        let volts = signal(&[(25.0, 0.5)], 0.0, 250.0, 250);
+        let spectrum = Spectrum::analyze(&volts, 250.0, WindowFunction::Rectangular);
+        assert_eq!(spectrum.bins.len(), 126);
+        assert!((spectrum.dominant_frequency - 25.0).abs() < 1e-3);
+        assert!((spectrum.dominant_magnitude - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_thd_estimate() {
+        let volts = signal(&[(50.0, 1.0), (100.0, 0.06), (150.0, 0.08)], 0.0, 1024.0, 1024);
+        let spectrum = Spectrum::analyze(&volts, 1024.0, WindowFunction::Hann);
+        let thd = spectrum.thd.expect("Harmonics are below Nyquist");
+        assert!((thd - 0.1).abs() < 0.005, "THD was {}", thd);
+
+        let pure = signal(&[(50.0, 1.0)], 0.0, 1024.0, 1024);
+        let spectrum = Spectrum::analyze(&pure, 1024.0, WindowFunction::Hann);
+        assert!(spectrum.thd.unwrap() < 1e-3);
+    }
+
+    #[test]
+    fn test_thd_low_fundamental() {
+        // Fundamental in bin 2: the second harmonic's lobe would reach back into it
+        let pure = signal(&[(2.0, 1.0)], 0.0, 64.0, 64);
+        let spectrum = Spectrum::analyze(&pure, 64.0, WindowFunction::Hann);
+        assert!((spectrum.dominant_frequency - 2.0).abs() < 1e-3);
+        assert!(spectrum.thd.unwrap() < 0.01, "THD was {:?}", spectrum.thd);
+    }
+
+    #[test]
+    fn test_missed_conversions() {
+        let sample = |seq| Sample { input: Mux::Single0, value: 0, volts: 0.0, timestamp: std::time::Instant::now(), seq };
+        let contiguous: Vec<Sample> = (5..10).map(sample).collect();
+        assert_eq!(Spectrum::missed_conversions(&contiguous), 0);
+
+        let gapped: Vec<Sample> = [0, 1, 4, 5, 7].iter().map(|&seq| sample(seq)).collect();
+        assert_eq!(Spectrum::missed_conversions(&gapped), 3);
+        assert_eq!(Spectrum::missed_conversions(&[]), 0);
+    }
+
+    #[test]
+    fn test_short_blocks() {
+        let spectrum = Spectrum::analyze(&[], 128.0, WindowFunction::Hann);
+        assert_eq!(spectrum.thd, None);
+        let spectrum = Spectrum::analyze(&[0.5], 128.0, WindowFunction::Hann);
+        assert_eq!(spectrum.bins[0].magnitude, 0.5);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_analyze_spectrum_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+
+        let spectrum = adc.analyze_spectrum(Mux::Single0, PGA::Two, SampleRates::S920Hz, 256, WindowFunction::Hann)
+            .expect("Failed to capture block");
+        let nyquist = adc.data_rate_hz(SampleRates::S920Hz) / 2.0;
+        assert_eq!(spectrum.bins.len(), 129);
+        assert!((spectrum.resolution - nyquist / 128.0).abs() < 1e-3);
+        assert!(spectrum.dominant_frequency > 0.0 && spectrum.dominant_frequency <= nyquist,
+            "Dominant frequency {} Hz is outside the spectrum", spectrum.dominant_frequency);
+        assert!(spectrum.dominant_magnitude >= 0.0 && spectrum.dominant_magnitude <= 2.048);
+        assert!(spectrum.bins[0].magnitude.abs() <= 2.048, "DC is the mean of the input");
+        if let Some(thd) = spectrum.thd {
+            assert!(thd >= 0.0);
+        }
+    }
+}
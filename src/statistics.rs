@@ -0,0 +1,282 @@
+//! Windowed statistics
+//!
+//! A [`StatsAccumulator`] summarises a stream of readings over a sliding window
+//! or over consecutive fixed-size blocks, reporting mean, variance, RMS and
+//! extremes in volts.
+
+use std::collections::VecDeque;
+
+use crate::{AdcError, Mux, Pointers, QwiicADC, PGA};
+
+/// How readings are grouped into statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsWindow {
+    /// Statistics of the last N readings, updated with every reading
+    Sliding(usize),
+    /// Statistics of consecutive, non-overlapping blocks of N readings
+    Block(usize),
+}
+
+/// Statistics of a window of readings, in volts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowStats {
+    /// Mean value
+    pub mean: f32,
+    /// Population variance (volts squared)
+    pub variance: f32,
+    /// Standard deviation, the RMS of the signal with its mean removed
+    pub std_dev: f32,
+    /// Root mean square, including the mean
+    pub rms: f32,
+    /// Smallest value
+    pub min: f32,
+    /// Largest value
+    pub max: f32,
+    /// Difference between the largest and smallest value
+    pub peak_to_peak: f32,
+    /// Number of readings in the window
+    pub count: usize,
+}
+
+/// Streaming statistics accumulator
+///
+/// Create one with `QwiicADC::stats_accumulator` to scale readings with the
+/// active gain, or with `new` to push values that are already in volts.
+#[derive(Debug, Clone)]
+pub struct StatsAccumulator {
+    window: StatsWindow,
+    volts_per_count: f32,
+    values: VecDeque<f32>,
+    sum: f64,
+    sum_squares: f64,
+    source: Option<(Mux, PGA)>,
+}
+
+impl StatsAccumulator {
+    /// Create an accumulator for values in volts
+    pub fn new(window: StatsWindow) -> StatsAccumulator {
+        StatsAccumulator::with_scale(window, 1.0)
+    }
+
+    /// Create an accumulator for signed counts
+    ///
+    /// # Arguments
+    /// * `window` - Sliding window or block size
+    /// * `volts_per_count` - Size of one count in volts
+    pub fn with_scale(window: StatsWindow, volts_per_count: f32) -> StatsAccumulator {
+        StatsAccumulator {
+            window,
+            volts_per_count,
+            values: VecDeque::with_capacity(window.size()),
+            sum: 0.0,
+            sum_squares: 0.0,
+            source: None,
+        }
+    }
+
+    /// Add a value in volts
+    ///
+    /// # Returns
+    /// * For a sliding window, the statistics of the last N values once N have been seen
+    /// * For blocks, the statistics of the block when it is complete
+    pub fn push(&mut self, volts: f32) -> Option<WindowStats> {
+        let size = self.window.size();
+        if self.values.len() == size {
+            let oldest = self.values.pop_front().unwrap_or(0.0) as f64;
+            self.sum -= oldest;
+            self.sum_squares -= oldest * oldest;
+        }
+        self.values.push_back(volts);
+        self.sum += volts as f64;
+        self.sum_squares += (volts as f64) * (volts as f64);
+
+        if self.values.len() < size {
+            return None;
+        }
+        let stats = self.stats();
+        if let StatsWindow::Block(_) = self.window {
+            self.reset();
+        }
+        stats
+    }
+
+    /// Add a signed conversion result, scaled to volts
+    pub fn push_counts(&mut self, counts: i16) -> Option<WindowStats> {
+        self.push(counts as f32 * self.volts_per_count)
+    }
+
+    /// Statistics of the values currently held, or `None` if there are none
+    pub fn stats(&self) -> Option<WindowStats> {
+        if self.values.is_empty() {
+            return None;
+        }
+
+        let count = self.values.len();
+        let mean = self.sum / count as f64;
+        // Running sums can go slightly negative through rounding
+        let variance = (self.sum_squares / count as f64 - mean * mean).max(0.0);
+        let min = self.values.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = self.values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+
+        Some(WindowStats {
+            mean: mean as f32,
+            variance: variance as f32,
+            std_dev: variance.sqrt() as f32,
+            rms: (self.sum_squares / count as f64).sqrt() as f32,
+            min,
+            max,
+            peak_to_peak: max - min,
+            count,
+        })
+    }
+
+    /// Discard all values
+    pub fn reset(&mut self) {
+        self.values.clear();
+        self.sum = 0.0;
+        self.sum_squares = 0.0;
+    }
+}
+
+impl StatsWindow {
+    /// Number of readings in the window
+    fn size(self) -> usize {
+        match self {
+            StatsWindow::Sliding(n) | StatsWindow::Block(n) => n.max(1),
+        }
+    }
+}
+
+impl QwiicADC {
+    /// Create a statistics accumulator scaled for the active gain
+    ///
+    /// # Arguments
+    /// * `window` - Sliding window or block size
+    pub fn stats_accumulator(&mut self, window: StatsWindow) -> Result<StatsAccumulator, AdcError> {
+        let (input, gain) = self.active_input_and_gain()?;
+        let mut stats = StatsAccumulator::with_scale(window, self.counts_to_volts(1.0, gain));
+        stats.source = Some((input, gain));
+        Ok(stats)
+    }
+
+    /// Add the last conversion result to a statistics accumulator
+    ///
+    /// The conversion goes through `check_conversion`, so it is calibrated and
+    /// counted in `clipping` if saturated. The input and gain are those active
+    /// when the accumulator was created, or at the first conversion added to an
+    /// accumulator created with `StatsAccumulator::new`.
+    ///
+    /// # Returns
+    /// See `StatsAccumulator::push`
+    pub fn accumulate_last_conversion(&mut self, stats: &mut StatsAccumulator) -> Result<Option<WindowStats>, AdcError> {
+        let raw = self.read_last_conversion()?;
+        let (input, gain) = match stats.source {
+            Some(source) => source,
+            None => *stats.source.insert(self.active_input_and_gain()?),
+        };
+        let reading = self.check_conversion(input, gain, raw);
+        Ok(stats.push(reading.volts))
+    }
+
+    /// Input and gain selected in the configuration register
+    fn active_input_and_gain(&mut self) -> Result<(Mux, PGA), AdcError> {
+        let config = self.read_register_16bit(Pointers::Config as u8)?;
+        let input = Mux::from_bits(config).expect("the MUX field selects one of eight inputs");
+        Ok((input, PGA::from_bits(config)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_block_statistics() {
+        let mut stats = StatsAccumulator::new(StatsWindow::Block(4));
+        assert_eq!(stats.push(1.0), None);
+        assert_eq!(stats.push(-1.0), None);
+        assert_eq!(stats.push(1.0), None);
+
+        let block = stats.push(-1.0).expect("Block should be complete");
+        assert_eq!(block.mean, 0.0);
+        assert_eq!(block.rms, 1.0);
+        assert_eq!(block.variance, 1.0);
+        assert_eq!(block.min, -1.0);
+        assert_eq!(block.max, 1.0);
+        assert_eq!(block.peak_to_peak, 2.0);
+        assert_eq!(block.count, 4);
+
+        // A new block starts empty
+        assert_eq!(stats.stats(), None);
+    }
+
+    #[test]
+    fn test_sliding_statistics() {
+        let mut stats = StatsAccumulator::new(StatsWindow::Sliding(3));
+        assert_eq!(stats.push(1.0), None);
+        assert_eq!(stats.push(2.0), None);
+        assert_eq!(stats.push(3.0).unwrap().mean, 2.0);
+
+        let window = stats.push(7.0).unwrap();
+        assert_eq!(window.mean, 4.0);
+        assert_eq!(window.min, 2.0);
+        assert_eq!(window.max, 7.0);
+        assert_eq!(window.count, 3);
+    }
+
+    #[test]
+    fn test_sine_rms() {
+        // A sine of amplitude A on a DC offset has AC RMS A/sqrt(2)
+        let mut stats = StatsAccumulator::new(StatsWindow::Block(1000));
+        let mut result = None;
+        for i in 0..1000 {
+            let volts = 0.5 + 1.0 * (2.0 * PI * 10.0 * i as f32 / 1000.0).sin();
+            result = stats.push(volts);
+        }
+
+        let result = result.expect("Block should be complete");
+        assert!((result.mean - 0.5).abs() < 1e-4);
+        assert!((result.std_dev - 1.0 / 2.0f32.sqrt()).abs() < 1e-3);
+        assert!((result.rms - (0.25f32 + 0.5).sqrt()).abs() < 1e-3);
+        assert!((result.peak_to_peak - 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_counts_are_scaled() {
+        // ADS1015 at PGA::Two: 1 mV per count
+        let mut stats = StatsAccumulator::with_scale(StatsWindow::Block(2), 0.001);
+        stats.push_counts(-1000);
+        let block = stats.push_counts(1000).unwrap();
+        assert!((block.peak_to_peak - 2.0).abs() < 1e-6);
+        assert!((block.rms - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_statistics_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+        adc.reset_clipping();
+
+        adc.start_continuous(0).expect("Failed to start continuous mode");
+        let mut stats = adc.stats_accumulator(StatsWindow::Block(32))
+            .expect("Failed to create accumulator");
+        // Wait long enough for each read to see a new conversion
+        let period = adc.settling_time(crate::SampleRates::S1600Hz as u16);
+        let mut result = None;
+        while result.is_none() {
+            std::thread::sleep(period);
+            result = adc.accumulate_last_conversion(&mut stats).expect("Failed to read");
+        }
+        adc.stop_continuous().expect("Failed to stop continuous mode");
+
+        let result = result.unwrap();
+        assert_eq!(result.count, 32);
+        assert!(result.min <= result.mean && result.mean <= result.max);
+        assert!(result.rms >= result.mean.abs() && result.std_dev <= result.peak_to_peak);
+        assert!(result.max <= 2.048 && result.min >= -2.048);
+        if result.max < 2.047 {
+            assert_eq!(adc.clipping().count(Mux::Single0), 0);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Continuous-mode streaming
+//!
+//! A [`Stream`] keeps the ADC in continuous conversion mode and paces reads of
+//! the conversion register to the configured data rate, so every conversion is
+//! returned at most once and conversions that were not read in time are
+//! reported through the sample sequence numbers.
+//!
+//! By default the pacing uses the host clock. The chip's internal oscillator is
+//! only accurate to ±10%, so over a long stream the two clocks drift apart and
+//! a conversion may be read twice or skipped without being counted. With
+//! `Stream::with_conversion_ready` each read waits for the ALERT/RDY pin instead
+//! and the timing is re-synchronized to the chip at every conversion.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{AdcError, Mux, QwiicADC, SampleRates, PGA};
+
+/// Extra time allowed after a conversion is due before reading it
+const CONVERSION_MARGIN: Duration = Duration::from_micros(50);
+
+/// A single conversion captured from a continuous-mode stream
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// Input the conversion was taken from
+    pub input: Mux,
+    /// Raw conversion result as returned by `read_last_conversion`
+    pub value: u16,
+    /// Conversion result in volts
+    pub volts: f32,
+    /// Time at which the conversion register was read
+    pub timestamp: Instant,
+    /// Index of the conversion since the stream was started
+    pub seq: u64,
+}
+
+/// Iterator over conversions of a single input in continuous mode
+///
+/// Created by `QwiicADC::stream` or `QwiicADC::stream_input`. The iterator never
+/// ends on its own; continuous mode is stopped when it is dropped.
+pub struct Stream<'a> {
+    adc: &'a mut QwiicADC,
+    input: Mux,
+    gain: PGA,
+    period: Duration,
+    started: Instant,
+    next_seq: u64,
+    missed: u64,
+    wait_ready: Option<Box<dyn FnMut() -> Result<(), AdcError> + 'a>>,
+}
+
+impl<'a> Stream<'a> {
+    /// Put the ADC in continuous mode and start timing conversions
+    pub(crate) fn start(adc: &'a mut QwiicADC, input: Mux, gain: PGA, rate: SampleRates) -> Result<Self, AdcError> {
+        let period = Duration::from_secs_f32(1.0 / adc.data_rate_hz(rate));
+        adc.start_continuous_input(input, gain, rate)?;
+
+        Ok(Stream {
+            adc,
+            input,
+            gain,
+            period,
+            started: Instant::now(),
+            next_seq: 0,
+            missed: 0,
+            wait_ready: None,
+        })
+    }
+
+    /// Pace reads by the ALERT/RDY pin instead of the host clock
+    ///
+    /// Configures the pin to pulse after every conversion (overwriting the
+    /// comparator thresholds). Each read then waits for a pulse, and the stream's
+    /// timing is re-synchronized to it so the host and chip clocks cannot drift
+    /// apart.
+    ///
+    /// # Arguments
+    /// * `wait_ready` - Blocks until the next conversion-ready pulse
+    pub fn with_conversion_ready<F>(mut self, wait_ready: F) -> Result<Self, AdcError>
+    where
+        F: FnMut() -> Result<(), AdcError> + 'a,
+    {
+        self.adc.enable_conversion_ready_pin()?;
+        self.started = Instant::now();
+        self.wait_ready = Some(Box::new(wait_ready));
+        Ok(self)
+    }
+
+    /// Input being streamed
+    pub fn input(&self) -> Mux {
+        self.input
+    }
+
+    /// Gain used for the conversions
+    pub fn gain(&self) -> PGA {
+        self.gain
+    }
+
+    /// Nominal time between conversions
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Number of conversions that completed without being read
+    ///
+    /// This is estimated from the time between reads. When paced by the host
+    /// clock the estimate drifts with the chip's oscillator over long streams;
+    /// see `with_conversion_ready`.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+impl Iterator for Stream<'_> {
+    type Item = Result<Sample, AdcError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(wait_ready) = self.wait_ready.as_mut() {
+            if let Err(e) = wait_ready() {
+                return Some(Err(e));
+            }
+        } else {
+            let due = self.started + conversion_due(self.next_seq, self.period) + CONVERSION_MARGIN;
+            let now = Instant::now();
+            if due > now {
+                thread::sleep(due - now);
+            }
+        }
+
+        let timestamp = Instant::now();
+        let value = match self.adc.read_last_conversion() {
+            Ok(value) => value,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let elapsed = timestamp.saturating_duration_since(self.started);
+        let seq = latest_conversion(elapsed, self.period)
+            .unwrap_or(self.next_seq)
+            .max(self.next_seq);
+        self.missed += seq - self.next_seq;
+        self.next_seq = seq + 1;
+        if self.wait_ready.is_some() {
+            // The pulse marks the end of conversion `seq`
+            self.started = timestamp.checked_sub(conversion_due(seq, self.period)).unwrap_or(self.started);
+        }
+
+        self.adc.count_clipping(self.input, value);
+        let volts = self.adc.counts_to_volts(self.adc.raw_to_signed(value) as f32, self.gain);
+        Some(Ok(Sample {
+            input: self.input,
+            value,
+            volts,
+            timestamp,
+            seq,
+        }))
+    }
+}
+
+impl Drop for Stream<'_> {
+    fn drop(&mut self) {
+        let _ = self.adc.stop_continuous();
+    }
+}
+
+/// Time after the start of continuous mode at which conversion `seq` completes
+fn conversion_due(seq: u64, period: Duration) -> Duration {
+    Duration::from_nanos((period.as_nanos() as u64).saturating_mul(seq + 1))
+}
+
+/// Index of the most recent conversion completed `elapsed` after the start,
+/// or `None` if the first conversion has not finished yet
+fn latest_conversion(elapsed: Duration, period: Duration) -> Option<u64> {
+    let completed = elapsed.as_nanos() / period.as_nanos().max(1);
+    (completed as u64).checked_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversion_due() {
+        let period = Duration::from_micros(625);  // 1600 SPS
+        assert_eq!(conversion_due(0, period), Duration::from_micros(625));
+        assert_eq!(conversion_due(3, period), Duration::from_micros(2500));
+    }
+
+    #[test]
+    fn test_latest_conversion() {
+        let period = Duration::from_millis(1);
+        assert_eq!(latest_conversion(Duration::from_micros(500), period), None);
+        assert_eq!(latest_conversion(Duration::from_micros(1000), period), Some(0));
+        assert_eq!(latest_conversion(Duration::from_micros(1999), period), Some(0));
+        assert_eq!(latest_conversion(Duration::from_micros(5200), period), Some(4));
+    }
+
+    #[test]
+    fn test_due_conversion_is_latest() {
+        // Reading at the due time of a conversion must see that conversion
+        let period = Duration::from_micros(1163);
+        for seq in 0..1000 {
+            let due = conversion_due(seq, period) + CONVERSION_MARGIN;
+            assert_eq!(latest_conversion(due, period), Some(seq));
+        }
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_stream_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+
+        let mut stream = adc.stream(0, SampleRates::S920Hz).expect("Failed to start stream");
+        let samples: Vec<Sample> = stream.by_ref().take(20)
+            .collect::<Result<_, _>>()
+            .expect("Failed to read stream");
+
+        for pair in samples.windows(2) {
+            assert!(pair[1].seq > pair[0].seq, "Sequence numbers must increase");
+        }
+        assert_eq!(stream.missed(), 0, "A short paced stream keeps up with the data rate");
+    }
+}
@@ -0,0 +1,353 @@
+//! NTC thermistor temperature measurement
+//!
+//! A [`Thermistor`] describes a thermistor in a voltage divider with a fixed
+//! series resistor. The divider voltage is converted to the thermistor's
+//! resistance and then to temperature with either the Beta equation or the
+//! Steinhart–Hart equation, whose coefficients can be solved from three
+//! calibration points.
+
+use crate::{AdcError, Mux, QwiicADC, Reading, SampleRates, PGA};
+
+/// Offset between the Celsius and Kelvin scales
+const ZERO_CELSIUS: f64 = 273.15;
+
+/// Unit of temperature results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemperatureUnit {
+    /// Degrees Celsius
+    Celsius,
+    /// Degrees Fahrenheit
+    Fahrenheit,
+    /// Kelvin
+    Kelvin,
+}
+
+impl TemperatureUnit {
+    /// Convert a temperature in kelvin to this unit
+    pub fn from_kelvin(self, kelvin: f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => kelvin - ZERO_CELSIUS as f32,
+            TemperatureUnit::Fahrenheit => (kelvin - ZERO_CELSIUS as f32) * 9.0 / 5.0 + 32.0,
+            TemperatureUnit::Kelvin => kelvin,
+        }
+    }
+
+    /// Convert a temperature in this unit to kelvin
+    pub fn to_kelvin(self, temperature: f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => temperature + ZERO_CELSIUS as f32,
+            TemperatureUnit::Fahrenheit => (temperature - 32.0) * 5.0 / 9.0 + ZERO_CELSIUS as f32,
+            TemperatureUnit::Kelvin => temperature,
+        }
+    }
+
+    /// Unit symbol
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+            TemperatureUnit::Kelvin => "K",
+        }
+    }
+}
+
+/// Position of the thermistor in the divider
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DividerTopology {
+    /// Thermistor between the input and ground, series resistor to the supply
+    ThermistorToGround,
+    /// Thermistor between the supply and the input, series resistor to ground
+    ThermistorToSupply,
+}
+
+/// Resistance to temperature relation of a thermistor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThermistorModel {
+    /// Beta equation `1/T = 1/T0 + ln(R/R0)/B`
+    Beta {
+        /// Beta coefficient in kelvin
+        beta: f32,
+        /// Resistance at the reference temperature in ohms
+        r0: f32,
+        /// Reference temperature in kelvin
+        t0: f32,
+    },
+    /// Steinhart–Hart equation `1/T = A + B ln(R) + C ln(R)^3`
+    SteinhartHart {
+        /// A coefficient
+        a: f64,
+        /// B coefficient
+        b: f64,
+        /// C coefficient
+        c: f64,
+    },
+}
+
+impl ThermistorModel {
+    /// Create a Beta model
+    ///
+    /// # Arguments
+    /// * `beta` - Beta coefficient in kelvin, e.g. 3950
+    /// * `r0` - Resistance at the reference temperature in ohms, e.g. 10000
+    /// * `t0_celsius` - Reference temperature in degrees Celsius, usually 25
+    pub fn beta(beta: f32, r0: f32, t0_celsius: f32) -> ThermistorModel {
+        ThermistorModel::Beta { beta, r0, t0: TemperatureUnit::Celsius.to_kelvin(t0_celsius) }
+    }
+
+    /// Solve Steinhart–Hart coefficients from three calibration points
+    ///
+    /// # Arguments
+    /// * `points` - (resistance in ohms, temperature in degrees Celsius) at three distinct temperatures
+    ///
+    /// # Returns
+    /// The model, or `None` if the points do not determine the coefficients
+    pub fn from_points(points: [(f32, f32); 3]) -> Option<ThermistorModel> {
+        let l: Vec<f64> = points.iter().map(|&(r, _)| (r as f64).ln()).collect();
+        let y: Vec<f64> = points.iter().map(|&(_, t)| 1.0 / (t as f64 + ZERO_CELSIUS)).collect();
+
+        let gamma2 = (y[1] - y[0]) / (l[1] - l[0]);
+        let gamma3 = (y[2] - y[0]) / (l[2] - l[0]);
+        let c = (gamma3 - gamma2) / (l[2] - l[1]) / (l[0] + l[1] + l[2]);
+        let b = gamma2 - c * (l[0] * l[0] + l[0] * l[1] + l[1] * l[1]);
+        let a = y[0] - (b + c * l[0] * l[0]) * l[0];
+
+        if a.is_finite() && b.is_finite() && c.is_finite() {
+            Some(ThermistorModel::SteinhartHart { a, b, c })
+        } else {
+            None
+        }
+    }
+
+    /// Temperature in kelvin at a resistance in ohms
+    pub fn kelvin(&self, resistance: f32) -> f32 {
+        match *self {
+            ThermistorModel::Beta { beta, r0, t0 } => 1.0 / (1.0 / t0 + (resistance / r0).ln() / beta),
+            ThermistorModel::SteinhartHart { a, b, c } => {
+                let l = (resistance as f64).ln();
+                (1.0 / (a + b * l + c * l * l * l)) as f32
+            },
+        }
+    }
+}
+
+/// A thermistor in a voltage divider
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thermistor {
+    /// Resistance to temperature relation
+    pub model: ThermistorModel,
+    /// Position of the thermistor in the divider (default: to ground)
+    pub topology: DividerTopology,
+    /// Fixed resistor in ohms
+    pub series_resistance: f32,
+    /// Divider supply in volts (default: 3.3)
+    pub supply_volts: f32,
+    /// Unit of results (default: Celsius)
+    pub unit: TemperatureUnit,
+}
+
+impl Thermistor {
+    /// Create a thermistor to ground with a series resistor to a 3.3 V supply
+    ///
+    /// # Arguments
+    /// * `model` - Resistance to temperature relation
+    /// * `series_resistance` - Fixed resistor in ohms
+    pub fn new(model: ThermistorModel, series_resistance: f32) -> Thermistor {
+        Thermistor {
+            model,
+            topology: DividerTopology::ThermistorToGround,
+            series_resistance,
+            supply_volts: 3.3,
+            unit: TemperatureUnit::Celsius,
+        }
+    }
+
+    /// Set the position of the thermistor in the divider
+    pub fn with_topology(mut self, topology: DividerTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Set the divider supply voltage
+    pub fn with_supply(mut self, volts: f32) -> Self {
+        self.supply_volts = volts;
+        self
+    }
+
+    /// Set the unit of results
+    pub fn with_unit(mut self, unit: TemperatureUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Thermistor resistance in ohms for a divider voltage
+    ///
+    /// # Returns
+    /// The resistance, or `None` if the voltage is not strictly between 0 and
+    /// the supply (an open or shorted thermistor)
+    pub fn resistance(&self, volts: f32) -> Option<f32> {
+        if volts <= 0.0 || volts >= self.supply_volts {
+            return None;
+        }
+        let remainder = self.supply_volts - volts;
+        Some(match self.topology {
+            DividerTopology::ThermistorToGround => self.series_resistance * volts / remainder,
+            DividerTopology::ThermistorToSupply => self.series_resistance * remainder / volts,
+        })
+    }
+
+    /// Temperature in kelvin for a divider voltage, see `resistance`
+    pub fn kelvin(&self, volts: f32) -> Option<f32> {
+        self.resistance(volts).map(|resistance| self.model.kelvin(resistance))
+    }
+
+    /// Temperature in the configured unit for a divider voltage, see `resistance`
+    pub fn temperature(&self, volts: f32) -> Option<f32> {
+        self.kelvin(volts).map(|kelvin| self.unit.from_kelvin(kelvin))
+    }
+
+    /// Temperature in the configured unit for a reading of the divider
+    ///
+    /// # Returns
+    /// The temperature, or `None` if the reading is saturated or the
+    /// thermistor reads open or shorted
+    pub fn reading_temperature(&self, reading: &Reading) -> Option<f32> {
+        if reading.is_saturated() {
+            None
+        } else {
+            self.temperature(reading.volts)
+        }
+    }
+
+    /// Narrowest PGA range that holds the full divider supply
+    pub fn gain(&self) -> PGA {
+        PGA::RANGES.iter()
+            .rev()
+            .copied()
+            .find(|gain| gain.full_scale_mv() / 1000.0 >= self.supply_volts)
+            .unwrap_or(PGA::TwoThirds)
+    }
+}
+
+impl QwiicADC {
+    /// Read the temperature of a thermistor on a single-ended channel
+    ///
+    /// The channel is read at the narrowest range that holds the divider
+    /// supply, see `Thermistor::gain`.
+    ///
+    /// # Arguments
+    /// * `channel` - Channel number (must be 0-3)
+    /// * `thermistor` - Divider and thermistor parameters
+    ///
+    /// # Returns
+    /// * `Ok(Some(temperature))` - Temperature in the thermistor's unit
+    /// * `Ok(None)` - The thermistor reads open or shorted, or the input is saturated
+    /// * `Err(AdcError::InvalidChannel)` if channel > 3
+    pub fn read_thermistor(&mut self, channel: u8, thermistor: &Thermistor) -> Result<Option<f32>, AdcError> {
+        let reading = self.read(Mux::single(channel)?, thermistor.gain(), SampleRates::S1600Hz)?;
+        Ok(thermistor.reading_temperature(&reading))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ntc_10k() -> ThermistorModel {
+        ThermistorModel::beta(3950.0, 10_000.0, 25.0)
+    }
+
+    #[test]
+    fn test_unit_conversions() {
+        assert!((TemperatureUnit::Celsius.from_kelvin(373.15) - 100.0).abs() < 1e-4);
+        assert!((TemperatureUnit::Fahrenheit.from_kelvin(373.15) - 212.0).abs() < 1e-3);
+        assert_eq!(TemperatureUnit::Kelvin.from_kelvin(300.0), 300.0);
+        assert!((TemperatureUnit::Fahrenheit.to_kelvin(32.0) - 273.15).abs() < 1e-4);
+        assert_eq!(TemperatureUnit::Celsius.symbol(), "°C");
+    }
+
+    #[test]
+    fn test_beta_model() {
+        let model = ntc_10k();
+        assert!((model.kelvin(10_000.0) - 298.15).abs() < 1e-3);
+        // 10k B3950 reads about 33.6k at 0 °C
+        let celsius = TemperatureUnit::Celsius.from_kelvin(model.kelvin(33_620.0));
+        assert!(celsius.abs() < 0.01, "Temperature was {}", celsius);
+    }
+
+    #[test]
+    fn test_divider_topologies() {
+        let low = Thermistor::new(ntc_10k(), 10_000.0).with_supply(3.0);
+        assert!((low.resistance(1.0).unwrap() - 5_000.0).abs() < 1e-2);
+        assert!((low.temperature(1.5).unwrap() - 25.0).abs() < 1e-3);
+
+        let high = low.with_topology(DividerTopology::ThermistorToSupply);
+        assert!((high.resistance(1.0).unwrap() - 20_000.0).abs() < 1e-2);
+
+        assert_eq!(low.resistance(0.0), None, "Shorted thermistor");
+        assert_eq!(low.resistance(3.0), None, "Open thermistor");
+    }
+
+    #[test]
+    fn test_steinhart_hart_solver() {
+        let beta = ntc_10k();
+        let points = [0.0f32, 25.0, 70.0].map(|celsius| {
+            // Invert the Beta equation for the resistance at each temperature
+            let kelvin = celsius as f64 + ZERO_CELSIUS;
+            let r = 10_000.0 * (3950.0 * (1.0 / kelvin - 1.0 / 298.15)).exp();
+            (r as f32, celsius)
+        });
+        let model = ThermistorModel::from_points(points).expect("Points should determine a model");
+
+        for (r, celsius) in points {
+            let solved = TemperatureUnit::Celsius.from_kelvin(model.kelvin(r));
+            assert!((solved - celsius).abs() < 0.01, "{} ohms gave {} °C", r, solved);
+        }
+        // Close to the Beta curve between the points
+        assert!((model.kelvin(5_000.0) - beta.kelvin(5_000.0)).abs() < 0.5);
+
+        assert_eq!(ThermistorModel::from_points([(10_000.0, 25.0); 3]), None);
+    }
+
+    #[test]
+    fn test_saturated_reading() {
+        let thermistor = Thermistor::new(ntc_10k(), 10_000.0);
+        assert_eq!(thermistor.gain(), PGA::One, "3.3 V needs the ±4.096 V range");
+        assert_eq!(thermistor.with_supply(2.0).gain(), PGA::Two);
+
+        // A cold thermistor clipped at the top of the ±2.048 V range
+        let mut reading = Reading {
+            input: Mux::Single0,
+            gain: PGA::Two,
+            counts: 0x7FFF,
+            volts: 2.048,
+            saturation: crate::Saturation::Positive,
+            range_exceeds_supply: false,
+        };
+        assert!(thermistor.temperature(reading.volts).is_some());
+        assert_eq!(thermistor.reading_temperature(&reading), None);
+
+        reading.saturation = crate::Saturation::None;
+        assert_eq!(thermistor.reading_temperature(&reading), thermistor.temperature(2.048));
+    }
+
+    #[test]
+    fn test_output_units() {
+        let thermistor = Thermistor::new(ntc_10k(), 10_000.0).with_unit(TemperatureUnit::Fahrenheit);
+        assert!((thermistor.temperature(1.65).unwrap() - 77.0).abs() < 1e-2);
+        let thermistor = thermistor.with_unit(TemperatureUnit::Kelvin);
+        assert!((thermistor.temperature(1.65).unwrap() - 298.15).abs() < 1e-2);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_thermistor_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::default());
+
+        let thermistor = Thermistor::new(ntc_10k(), 10_000.0);
+        let temperature = adc.read_thermistor(0, &thermistor)
+            .expect("Failed to read")
+            .expect("Thermistor reads open, shorted or saturated");
+        assert!((-40.0..=125.0).contains(&temperature), "Temperature was {} °C", temperature);
+        assert!(adc.read_thermistor(4, &thermistor).is_err(), "Channel 4 does not exist");
+    }
+}
@@ -0,0 +1,421 @@
+//! Thermocouple temperature measurement
+//!
+//! Converts thermocouple EMF to temperature with the NIST ITS-90 polynomials
+//! for types K, J, T and E. The cold junction is compensated by adding the EMF
+//! the thermocouple would produce at the cold-junction temperature, which is
+//! either fixed or read from a thermistor on another channel. An input driven
+//! to full scale (by a bias resistor on an open thermocouple) is reported as an
+//! open circuit.
+
+use crate::{AdcError, Mux, QwiicADC, SampleRates, TemperatureUnit, Thermistor, PGA};
+
+/// Polynomial over a temperature (°C) or EMF (mV) range
+struct Range {
+    /// Lower limit of the range
+    low: f64,
+    /// Upper limit of the range
+    high: f64,
+    /// Coefficients in ascending order of power
+    coefficients: &'static [f64],
+}
+
+/// Evaluate the polynomial of the range containing `x`
+fn evaluate(ranges: &[Range], x: f64) -> Option<f64> {
+    let range = ranges.iter().find(|range| x >= range.low && x <= range.high)?;
+    Some(range.coefficients.iter().rev().fold(0.0, |acc, &c| acc * x + c))
+}
+
+/// Type K exponential term of the reference function above 0 °C
+const K_EXPONENTIAL: [f64; 3] = [0.118_597_600_000, -0.118_343_200_000e-3, 0.126_968_600_000e3];
+
+/// Type K EMF in millivolts from temperature in degrees Celsius
+const K_REFERENCE: [Range; 2] = [
+    Range { low: -270.0, high: 0.0, coefficients: &[
+        0.0, 0.394_501_280_250e-1, 0.236_223_735_980e-4, -0.328_589_067_840e-6,
+        -0.499_048_287_770e-8, -0.675_090_591_730e-10, -0.574_103_274_280e-12,
+        -0.310_888_728_940e-14, -0.104_516_093_650e-16, -0.198_892_668_780e-19,
+        -0.163_226_974_860e-22,
+    ] },
+    Range { low: 0.0, high: 1372.0, coefficients: &[
+        -0.176_004_136_860e-1, 0.389_212_049_750e-1, 0.185_587_700_320e-4,
+        -0.994_575_928_740e-7, 0.318_409_457_190e-9, -0.560_728_448_890e-12,
+        0.560_750_590_590e-15, -0.320_207_200_030e-18, 0.971_511_471_520e-22,
+        -0.121_047_212_750e-25,
+    ] },
+];
+
+/// Type K temperature in degrees Celsius from EMF in millivolts
+const K_INVERSE: [Range; 3] = [
+    Range { low: -5.891, high: 0.0, coefficients: &[
+        0.0, 2.517_346_2e1, -1.166_287_8, -1.083_363_8, -8.977_354_0e-1,
+        -3.734_237_7e-1, -8.663_264_3e-2, -1.045_059_8e-2, -5.192_057_7e-4,
+    ] },
+    Range { low: 0.0, high: 20.644, coefficients: &[
+        0.0, 2.508_355e1, 7.860_106e-2, -2.503_131e-1, 8.315_270e-2,
+        -1.228_034e-2, 9.804_036e-4, -4.413_030e-5, 1.057_734e-6, -1.052_755e-8,
+    ] },
+    Range { low: 20.644, high: 54.886, coefficients: &[
+        -1.318_058e2, 4.830_222e1, -1.646_031, 5.464_731e-2, -9.650_715e-4,
+        8.802_193e-6, -3.110_810e-8,
+    ] },
+];
+
+/// Type J EMF in millivolts from temperature in degrees Celsius
+const J_REFERENCE: [Range; 2] = [
+    Range { low: -210.0, high: 760.0, coefficients: &[
+        0.0, 0.503_811_878_150e-1, 0.304_758_369_300e-4, -0.856_810_657_200e-7,
+        0.132_281_952_950e-9, -0.170_529_583_370e-12, 0.209_480_906_970e-15,
+        -0.125_383_953_360e-18, 0.156_317_256_970e-22,
+    ] },
+    Range { low: 760.0, high: 1200.0, coefficients: &[
+        0.296_456_256_810e3, -0.149_761_277_860e1, 0.317_871_039_240e-2,
+        -0.318_476_867_010e-5, 0.157_208_190_040e-8, -0.306_913_690_560e-12,
+    ] },
+];
+
+/// Type J temperature in degrees Celsius from EMF in millivolts
+const J_INVERSE: [Range; 3] = [
+    Range { low: -8.095, high: 0.0, coefficients: &[
+        0.0, 1.952_826_8e1, -1.228_618_5, -1.075_217_8, -5.908_693_3e-1,
+        -1.725_671_3e-1, -2.813_151_3e-2, -2.396_337_0e-3, -8.382_332_1e-5,
+    ] },
+    Range { low: 0.0, high: 42.919, coefficients: &[
+        0.0, 1.978_425e1, -2.001_204e-1, 1.036_969e-2, -2.549_687e-4,
+        3.585_153e-6, -5.344_285e-8, 5.099_890e-10,
+    ] },
+    Range { low: 42.919, high: 69.553, coefficients: &[
+        -3.113_581_87e3, 3.005_436_84e2, -9.947_732_30, 1.702_766_30e-1,
+        -1.430_334_68e-3, 4.738_860_84e-6,
+    ] },
+];
+
+/// Type T EMF in millivolts from temperature in degrees Celsius
+const T_REFERENCE: [Range; 2] = [
+    Range { low: -270.0, high: 0.0, coefficients: &[
+        0.0, 0.387_481_063_640e-1, 0.441_944_343_470e-4, 0.118_443_231_050e-6,
+        0.200_329_735_540e-7, 0.901_380_195_590e-9, 0.226_511_565_930e-10,
+        0.360_711_542_050e-12, 0.384_939_398_830e-14, 0.282_135_219_250e-16,
+        0.142_515_947_790e-18, 0.487_686_622_860e-21, 0.107_955_392_700e-23,
+        0.139_450_270_620e-26, 0.797_951_539_270e-30,
+    ] },
+    Range { low: 0.0, high: 400.0, coefficients: &[
+        0.0, 0.387_481_063_640e-1, 0.332_922_278_800e-4, 0.206_182_434_040e-6,
+        -0.218_822_568_460e-8, 0.109_968_809_280e-10, -0.308_157_587_720e-13,
+        0.454_791_352_900e-16, -0.275_129_016_730e-19,
+    ] },
+];
+
+/// Type T temperature in degrees Celsius from EMF in millivolts
+const T_INVERSE: [Range; 2] = [
+    Range { low: -5.603, high: 0.0, coefficients: &[
+        0.0, 2.594_919_2e1, -2.131_696_7e-1, 7.901_869_2e-1, 4.252_777_7e-1,
+        1.330_447_3e-1, 2.024_144_6e-2, 1.266_817_1e-3,
+    ] },
+    Range { low: 0.0, high: 20.872, coefficients: &[
+        0.0, 2.592_800e1, -7.602_961e-1, 4.637_791e-2, -2.165_394e-3,
+        6.048_144e-5, -7.293_422e-7,
+    ] },
+];
+
+/// Type E EMF in millivolts from temperature in degrees Celsius
+const E_REFERENCE: [Range; 2] = [
+    Range { low: -270.0, high: 0.0, coefficients: &[
+        0.0, 0.586_655_087_080e-1, 0.454_109_771_240e-4, -0.779_980_486_860e-6,
+        -0.258_001_608_430e-7, -0.594_525_830_570e-9, -0.932_140_586_670e-11,
+        -0.102_876_055_340e-12, -0.803_701_236_210e-15, -0.439_794_973_910e-17,
+        -0.164_147_763_550e-19, -0.396_736_195_160e-22, -0.558_273_287_210e-25,
+        -0.346_578_420_130e-28,
+    ] },
+    Range { low: 0.0, high: 1000.0, coefficients: &[
+        0.0, 0.586_655_087_100e-1, 0.450_322_755_820e-4, 0.289_084_072_120e-7,
+        -0.330_568_966_520e-9, 0.650_244_032_700e-12, -0.191_974_955_040e-15,
+        -0.125_366_004_970e-17, 0.214_892_175_690e-20, -0.143_880_417_820e-23,
+        0.359_608_994_810e-27,
+    ] },
+];
+
+/// Type E temperature in degrees Celsius from EMF in millivolts
+const E_INVERSE: [Range; 2] = [
+    Range { low: -8.825, high: 0.0, coefficients: &[
+        0.0, 1.697_728_8e1, -4.351_497_0e-1, -1.585_969_7e-1, -9.250_287_1e-2,
+        -2.608_431_4e-2, -4.136_019_9e-3, -3.403_403_0e-4, -1.156_489_0e-5,
+    ] },
+    Range { low: 0.0, high: 76.373, coefficients: &[
+        0.0, 1.705_703_5e1, -2.330_175_9e-1, 6.543_558_5e-3, -7.356_274_9e-5,
+        -1.789_600_1e-6, 8.403_616_5e-8, -1.373_587_9e-9, 1.062_982_3e-11,
+        -3.244_708_7e-14,
+    ] },
+];
+
+/// Thermocouple type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThermocoupleType {
+    /// Chromel–alumel, -270 to 1372 °C
+    K,
+    /// Iron–constantan, -210 to 1200 °C
+    J,
+    /// Copper–constantan, -270 to 400 °C
+    T,
+    /// Chromel–constantan, -270 to 1000 °C
+    E,
+}
+
+impl ThermocoupleType {
+    /// EMF in millivolts at a temperature, with the reference junction at 0 °C
+    ///
+    /// # Returns
+    /// The EMF, or `None` outside the type's temperature range
+    pub fn emf_mv(self, celsius: f32) -> Option<f32> {
+        let t = celsius as f64;
+        let emf = match self {
+            ThermocoupleType::K => {
+                let [a0, a1, a2] = K_EXPONENTIAL;
+                let exponential = if t > 0.0 { a0 * (a1 * (t - a2).powi(2)).exp() } else { 0.0 };
+                evaluate(&K_REFERENCE, t)? + exponential
+            },
+            ThermocoupleType::J => evaluate(&J_REFERENCE, t)?,
+            ThermocoupleType::T => evaluate(&T_REFERENCE, t)?,
+            ThermocoupleType::E => evaluate(&E_REFERENCE, t)?,
+        };
+        Some(emf as f32)
+    }
+
+    /// Temperature in degrees Celsius for an EMF in millivolts, with the
+    /// reference junction at 0 °C
+    ///
+    /// # Returns
+    /// The temperature, or `None` outside the range of the inverse polynomials
+    pub fn celsius(self, emf_mv: f32) -> Option<f32> {
+        let ranges: &[Range] = match self {
+            ThermocoupleType::K => &K_INVERSE,
+            ThermocoupleType::J => &J_INVERSE,
+            ThermocoupleType::T => &T_INVERSE,
+            ThermocoupleType::E => &E_INVERSE,
+        };
+        evaluate(ranges, emf_mv as f64).map(|t| t as f32)
+    }
+}
+
+/// Source of the cold-junction temperature
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColdJunction {
+    /// Fixed temperature in degrees Celsius
+    Fixed(f32),
+    /// Thermistor on a single-ended channel, read with `read_thermistor`
+    Thermistor {
+        /// Channel number (must be 0-3)
+        channel: u8,
+        /// Thermistor parameters
+        thermistor: Thermistor,
+    },
+}
+
+/// Outcome of a thermocouple reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThermocoupleStatus {
+    /// The temperature is valid
+    Ok,
+    /// The input is at full scale, as with a broken thermocouple
+    OpenCircuit,
+    /// The EMF is outside the type's range
+    OutOfRange,
+    /// The cold-junction thermistor reads open or shorted
+    ColdJunctionFault,
+}
+
+/// A thermocouple reading
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThermocoupleReading {
+    /// Hot-junction temperature in the thermocouple's unit, if the status is `Ok`
+    pub temperature: Option<f32>,
+    /// Cold-junction temperature in the thermocouple's unit, if known
+    pub cold_junction: Option<f32>,
+    /// Measured EMF in millivolts
+    pub emf_mv: f32,
+    /// Outcome of the reading
+    pub status: ThermocoupleStatus,
+}
+
+/// Thermocouple settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thermocouple {
+    /// Thermocouple type
+    pub kind: ThermocoupleType,
+    /// Differential input of the thermocouple (default: `Mux::DiffP0N1`)
+    pub input: Mux,
+    /// PGA gain setting (default: `PGA::Sixteen`, ±256 mV)
+    pub gain: PGA,
+    /// Data rate (default: the slowest)
+    pub rate: SampleRates,
+    /// Cold-junction temperature source (default: fixed at 25 °C)
+    pub cold_junction: ColdJunction,
+    /// Unit of results (default: Celsius)
+    pub unit: TemperatureUnit,
+}
+
+impl Thermocouple {
+    /// Create a thermocouple on `Mux::DiffP0N1` with the cold junction at 25 °C
+    pub fn new(kind: ThermocoupleType) -> Thermocouple {
+        Thermocouple {
+            kind,
+            input: Mux::DiffP0N1,
+            gain: PGA::Sixteen,
+            rate: SampleRates::S128Hz,
+            cold_junction: ColdJunction::Fixed(25.0),
+            unit: TemperatureUnit::Celsius,
+        }
+    }
+
+    /// Set the differential input
+    pub fn with_input(mut self, input: Mux) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// Set the PGA gain setting
+    pub fn with_gain(mut self, gain: PGA) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Set the data rate
+    pub fn with_sample_rate(mut self, rate: SampleRates) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    /// Set the cold-junction temperature source
+    pub fn with_cold_junction(mut self, cold_junction: ColdJunction) -> Self {
+        self.cold_junction = cold_junction;
+        self
+    }
+
+    /// Set the unit of results
+    pub fn with_unit(mut self, unit: TemperatureUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Hot-junction temperature in degrees Celsius from the measured EMF
+    ///
+    /// # Arguments
+    /// * `emf_mv` - Measured EMF in millivolts
+    /// * `cold_junction` - Cold-junction temperature in degrees Celsius
+    ///
+    /// # Returns
+    /// The temperature, or `None` if it is outside the type's range
+    pub fn compensate(&self, emf_mv: f32, cold_junction: f32) -> Option<f32> {
+        self.kind.celsius(emf_mv + self.kind.emf_mv(cold_junction)?)
+    }
+}
+
+impl QwiicADC {
+    /// Read the temperature of a thermocouple
+    ///
+    /// # Arguments
+    /// * `thermocouple` - Thermocouple settings and cold-junction source
+    pub fn read_thermocouple(&mut self, thermocouple: &Thermocouple) -> Result<ThermocoupleReading, AdcError> {
+        let cold_junction = match thermocouple.cold_junction {
+            ColdJunction::Fixed(celsius) => Some(celsius),
+            ColdJunction::Thermistor { channel, thermistor } => self.read_thermistor(channel, &thermistor)?
+                .map(|t| TemperatureUnit::Celsius.from_kelvin(thermistor.unit.to_kelvin(t))),
+        };
+
+        let reading = self.read(thermocouple.input, thermocouple.gain, thermocouple.rate)?;
+        let emf_mv = reading.volts * 1000.0;
+
+        let (temperature, status) = if reading.is_saturated() {
+            (None, ThermocoupleStatus::OpenCircuit)
+        } else if let Some(cold_junction) = cold_junction {
+            match thermocouple.compensate(emf_mv, cold_junction) {
+                Some(celsius) => (Some(celsius), ThermocoupleStatus::Ok),
+                None => (None, ThermocoupleStatus::OutOfRange),
+            }
+        } else {
+            (None, ThermocoupleStatus::ColdJunctionFault)
+        };
+
+        let convert = |celsius: f32| thermocouple.unit.from_kelvin(TemperatureUnit::Celsius.to_kelvin(celsius));
+        Ok(ThermocoupleReading {
+            temperature: temperature.map(convert),
+            cold_junction: cold_junction.map(convert),
+            emf_mv,
+            status,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// NIST ITS-90 table values (°C, mV)
+    const TABLE: [(ThermocoupleType, f32, f32); 14] = [
+        (ThermocoupleType::K, -100.0, -3.554),
+        (ThermocoupleType::K, 100.0, 4.096),
+        (ThermocoupleType::K, 500.0, 20.644),
+        (ThermocoupleType::K, 1000.0, 41.276),
+        (ThermocoupleType::J, -100.0, -4.633),
+        (ThermocoupleType::J, 100.0, 5.269),
+        (ThermocoupleType::J, 500.0, 27.393),
+        (ThermocoupleType::J, 1000.0, 57.953),
+        (ThermocoupleType::T, -100.0, -3.379),
+        (ThermocoupleType::T, 100.0, 4.279),
+        (ThermocoupleType::T, 200.0, 9.288),
+        (ThermocoupleType::E, -100.0, -5.237),
+        (ThermocoupleType::E, 100.0, 6.319),
+        (ThermocoupleType::E, 500.0, 37.005),
+    ];
+
+    #[test]
+    fn test_reference_functions() {
+        for (kind, celsius, emf) in TABLE {
+            let computed = kind.emf_mv(celsius).unwrap();
+            assert!((computed - emf).abs() < 0.001, "{:?} at {} °C: {} mV", kind, celsius, computed);
+        }
+    }
+
+    #[test]
+    fn test_inverse_functions() {
+        for (kind, celsius, emf) in TABLE {
+            let computed = kind.celsius(emf).unwrap();
+            assert!((computed - celsius).abs() < 0.1, "{:?} at {} mV: {} °C", kind, emf, computed);
+        }
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(ThermocoupleType::T.emf_mv(450.0), None);
+        assert_eq!(ThermocoupleType::K.celsius(60.0), None);
+        assert!(ThermocoupleType::K.emf_mv(0.0).unwrap().abs() < 1e-4);
+        assert!(ThermocoupleType::K.emf_mv(0.001).unwrap().abs() < 1e-4, "Continuous at 0 °C");
+    }
+
+    #[test]
+    fn test_cold_junction_compensation() {
+        // K at 100 °C with the cold junction at 25 °C produces 4.096 - 1.000 mV
+        let thermocouple = Thermocouple::new(ThermocoupleType::K);
+        let emf = 4.096 - ThermocoupleType::K.emf_mv(25.0).unwrap();
+        assert!((ThermocoupleType::K.emf_mv(25.0).unwrap() - 1.000).abs() < 0.001);
+        assert!((thermocouple.compensate(emf, 25.0).unwrap() - 100.0).abs() < 0.1);
+        assert_eq!(thermocouple.compensate(0.0, 2000.0), None);
+    }
+
+    #[test]
+    #[ignore] // Requires hardware
+    fn test_thermocouple_hardware() {
+        let mut adc = crate::test_device(crate::QwiicADCConfig::new("ADS1115".to_string()));
+
+        let thermistor = Thermistor::new(crate::ThermistorModel::beta(3950.0, 10_000.0, 25.0), 10_000.0);
+        let thermocouple = Thermocouple::new(ThermocoupleType::K)
+            .with_cold_junction(ColdJunction::Thermistor { channel: 2, thermistor });
+        let reading = adc.read_thermocouple(&thermocouple).expect("Failed to read");
+        assert_ne!(reading.status, ThermocoupleStatus::ColdJunctionFault);
+        let cold_junction = reading.cold_junction.expect("No cold-junction temperature");
+        assert!((-40.0..=125.0).contains(&cold_junction), "Cold junction at {} °C", cold_junction);
+        assert_eq!(reading.temperature.is_some(), reading.status == ThermocoupleStatus::Ok);
+        if let Some(temperature) = reading.temperature {
+            assert!((-270.0..=1372.0).contains(&temperature), "Type K reads {} °C", temperature);
+        }
+    }
+}